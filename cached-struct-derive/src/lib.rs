@@ -0,0 +1,111 @@
+#![warn(clippy::all, missing_docs)]
+//! Derive macro for the `Cache` trait of `cached-struct`
+//!
+//! Use it through the `derive` feature of `cached-struct` rather than depending on this directly.
+
+use proc_macro::TokenStream;
+use proc_macro2::TokenStream as TokenStream2;
+use quote::quote;
+use syn::{parse_macro_input, DeriveInput, Error, LitStr, Path, Result};
+
+/// How the generated implementation serializes the type
+enum Format {
+    /// `serde_json`, needing the `serde` feature
+    Json,
+    /// `bincode`, needing the `bincode` feature
+    Bincode,
+    /// A pair of user-supplied functions
+    Custom { save: Path, load: Path },
+}
+
+impl Format {
+    fn from_attrs(input: &DeriveInput) -> Result<Self> {
+        let mut format = None;
+        let mut save = None;
+        let mut load = None;
+
+        for attr in input.attrs.iter().filter(|a| a.path().is_ident("cache")) {
+            attr.parse_nested_meta(|meta| {
+                let value: LitStr = meta.value()?.parse()?;
+                if meta.path.is_ident("format") {
+                    format = Some(match &*value.value() {
+                        "json" => Format::Json,
+                        "bincode" => Format::Bincode,
+                        _ => return Err(Error::new(value.span(), "unknown format, expected \"json\" or \"bincode\"")),
+                    });
+                } else if meta.path.is_ident("save") {
+                    save = Some(value.parse()?);
+                } else if meta.path.is_ident("load") {
+                    load = Some(value.parse()?);
+                } else {
+                    return Err(meta.error("unknown cache attribute, expected `format`, `save` or `load`"));
+                }
+                Ok(())
+            })?;
+        }
+
+        match (format, save, load) {
+            (Some(format), None, None) => Ok(format),
+            (None, Some(save), Some(load)) => Ok(Format::Custom { save, load }),
+            (Some(_), _, _) => Err(Error::new_spanned(input, "`format` can't be combined with `save` and `load`")),
+            (None, None, None) => Err(Error::new_spanned(
+                input,
+                "expected `#[cache(format = \"...\")]` or `#[cache(save = \"...\", load = \"...\")]`",
+            )),
+            (None, _, _) => Err(Error::new_spanned(input, "both `save` and `load` have to be given")),
+        }
+    }
+}
+
+/// Implements `Cache` for a type using a format chosen with the `cache` attribute
+///
+/// - `#[cache(format = "json")]` stores the type as JSON, needing the `serde` feature
+/// - `#[cache(format = "bincode")]` stores the type with bincode, needing the `bincode` feature
+/// - `#[cache(save = "path::to::save", load = "path::to::load")]` uses the given functions,
+///   with the same signatures as `Cache::save` and `Cache::load` taking the value as the first argument
+///
+/// The serde formats need the type to implement `Serialize` and `DeserializeOwned`.
+#[proc_macro_derive(Cache, attributes(cache))]
+pub fn derive_cache(input: TokenStream) -> TokenStream {
+    let input = parse_macro_input!(input as DeriveInput);
+    expand(&input).unwrap_or_else(Error::into_compile_error).into()
+}
+
+fn expand(input: &DeriveInput) -> Result<TokenStream2> {
+    let name = &input.ident;
+    let (impl_generics, ty_generics, where_clause) = input.generics.split_for_impl();
+    let mut where_clause = where_clause.cloned().unwrap_or_else(|| syn::parse_quote!(where));
+
+    let private = quote!(::cached_struct::__private);
+    let format = Format::from_attrs(input)?;
+    if let Format::Json | Format::Bincode = format {
+        where_clause.predicates.push(syn::parse_quote!(
+            Self: #private::serde::Serialize + #private::serde::de::DeserializeOwned
+        ));
+    }
+    let (save, load) = match format {
+        Format::Json => (
+            quote!(#private::serde_json::to_writer(writer, self).map_err(::std::io::Error::from)),
+            quote!(#private::serde_json::from_reader(reader).map_err(::std::io::Error::from)),
+        ),
+        Format::Bincode => (
+            quote!(#private::bincode::serialize_into(writer, self).map_err(::std::io::Error::other)),
+            quote!(#private::bincode::deserialize_from(reader).map_err(::std::io::Error::other)),
+        ),
+        Format::Custom { save, load } => (quote!(#save(self, writer)), quote!(#load(reader))),
+    };
+
+    Ok(quote! {
+        impl #impl_generics ::cached_struct::Cache for #name #ty_generics #where_clause {
+            fn save<W: ::std::io::Write>(&self, writer: W) -> ::std::io::Result<()> {
+                #save
+            }
+            fn load<R: ::std::io::Read>(reader: R) -> ::std::io::Result<Self> {
+                Self::load_buf(::std::io::BufReader::new(reader))
+            }
+            fn load_buf<R: ::std::io::BufRead>(reader: R) -> ::std::io::Result<Self> {
+                #load
+            }
+        }
+    })
+}
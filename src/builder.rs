@@ -0,0 +1,701 @@
+use std::{
+    cell::{Cell, RefCell},
+    fmt,
+    io,
+    mem,
+    marker::PhantomData,
+    sync::{Arc, atomic::AtomicBool},
+    path::PathBuf,
+    time::{Duration, SystemTime},
+};
+
+use crate::{Cache, Cached, Clock, ReadOnlyCached, Result, persist, storage::{FileStorage, Storage}};
+
+/// Options set through the builder that affect how the stored data is handled
+#[derive(Debug, Clone, Default)]
+pub(crate) struct Options {
+    /// How long after a mutation to wait before saving, if saves are debounced
+    pub debounce: Option<Duration>,
+    /// Minimum time between saves after mutations, if saves are throttled
+    pub max_save_rate: Option<Duration>,
+    /// Minimum time between checks of whether the stored data has changed
+    pub check_interval: Option<Duration>,
+    /// Whether changes are detected by hashing the stored data instead of from its metadata
+    pub hash_based_reload: bool,
+    /// How long the inner value stays fresh after being loaded or saved, regardless of the metadata
+    pub ttl: Option<Duration>,
+    /// How long changed stored data has to stay unchanged before it is loaded, if waiting for it to settle
+    pub settle_delay: Option<Duration>,
+    /// How many times to retry loading data that seems to be partially written
+    pub load_retries: usize,
+    /// How many times to retry a full save that fails with a transient error
+    pub save_retries: usize,
+    /// How long to wait before the first retry of a save, doubling with each retry
+    pub save_backoff: Duration,
+    /// Whether the inner value is reset when the stored data disappears
+    pub reset_on_missing: bool,
+    /// Whether empty stored data is taken as the default value instead of being loaded
+    pub empty_as_default: bool,
+    /// Whether nothing is ever written, for read-only instances
+    pub read_only: bool,
+    /// Whether unsaved changes are saved when the instance is dropped
+    pub flush_on_drop: bool,
+    /// Largest stored data in bytes that is loaded, if limited
+    pub max_size: Option<u64>,
+    /// Gives the current time instead of the system clock, if set
+    pub clock: Option<Arc<dyn Clock>>,
+    /// What to do when loading the stored data fails
+    pub load_error_policy: LoadErrorPolicy,
+    /// Flag that makes saves fail partway when set
+    pub cancel: Option<Arc<AtomicBool>>,
+    /// Whether to only keep the value in memory if the storage can't be written to
+    pub fallback_in_memory: bool,
+    /// Whether the storage is never touched, since it couldn't be written to
+    pub in_memory: bool,
+    /// Capacity of the buffers for loading and saving, if not the default
+    pub buffer_size: Option<usize>,
+    /// Whether space for the data is reserved before writing it
+    pub preallocate: bool,
+}
+
+/// What to do when the stored data fails to load, see [`CachedBuilder::on_load_error`]
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash)]
+pub enum LoadErrorPolicy {
+    /// Return the error, as is the default
+    #[default]
+    Propagate,
+    /// Use the default value instead, leaving the stored data as it is until the next save
+    UseDefault,
+    /// Use the default value instead, and save it over the stored data right away
+    UseDefaultAndOverwrite,
+}
+
+/// Builder for configuring how a [`Cached`] is constructed
+///
+/// Created using [`Cached::builder`], [`Cached::builder_with`] or [`Cached::builder_with_storage`],
+/// or with [`CachedBuilder::new`] for a custom storage and default function.
+#[must_use = "a builder does nothing until `build` is called"]
+pub struct CachedBuilder<T, F = fn() -> T, S = FileStorage> {
+    storage: S,
+    default: F,
+    options: Options,
+    create_if_missing: bool,
+    snapshot: Option<fn(&T) -> T>,
+    on_reload: Option<ReloadHook<T>>,
+    retention: Option<RetentionHook<T>>,
+    validator: Option<Validator<T>>,
+    changed: Option<ChangedPredicate<T>>,
+    factory: Option<Factory<T>>,
+    _marker: PhantomData<fn() -> T>,
+}
+
+/// Callback run after the inner value has been loaded from the stored data
+pub(crate) struct ReloadHook<T>(pub Box<dyn Fn(&T) + Send>);
+
+impl<T> fmt::Debug for ReloadHook<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ReloadHook(..)")
+    }
+}
+
+/// Callback trimming the inner value before it is saved
+pub(crate) struct RetentionHook<T>(pub Box<dyn Fn(&mut T) + Send>);
+
+impl<T> fmt::Debug for RetentionHook<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("RetentionHook(..)")
+    }
+}
+
+/// Callback checking the inner value before it is saved
+pub(crate) struct Validator<T>(pub Box<Validate<T>>);
+
+type Validate<T> = dyn Fn(&T) -> io::Result<()> + Send;
+
+impl<T> fmt::Debug for Validator<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Validator(..)")
+    }
+}
+
+/// Decides whether the inner value has changed from the one last loaded or saved, which is kept as a clone
+pub(crate) struct ChangedPredicate<T> {
+    pub changed: Box<Changed<T>>,
+    pub clone: fn(&T) -> T,
+}
+
+type Changed<T> = dyn Fn(&T, &T) -> bool + Send;
+
+impl<T> fmt::Debug for ChangedPredicate<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("ChangedPredicate(..)")
+    }
+}
+
+/// Gives the default value again whenever it's needed, such as for resetting
+pub(crate) enum Factory<T> {
+    /// The type's `Default` implementation
+    Default(fn() -> T),
+    /// A function set with [`CachedBuilder::default_factory`]
+    Custom(Box<dyn Fn() -> T + Send>),
+}
+
+impl<T> Factory<T> {
+    pub fn make(&self) -> T {
+        match self {
+            Factory::Default(f) => f(),
+            Factory::Custom(f) => f(),
+        }
+    }
+}
+
+impl<T> fmt::Debug for Factory<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            Factory::Default(_) => f.write_str("Default"),
+            Factory::Custom(_) => f.write_str("Custom(..)"),
+        }
+    }
+}
+
+impl<T: Cache, F: FnOnce() -> T> CachedBuilder<T, F> {
+    /// Sets whether saves are written to a temporary file that is then renamed over the cache file,
+    /// so that a crash or failed save can't leave the cache file partially written
+    ///
+    /// This also means that other instances reading the cache file while it is saved,
+    /// in this or another process, load either the whole old value or the whole new one.
+    /// Enabled by default.
+    pub fn atomic_writes(mut self, atomic_writes: bool) -> Self {
+        self.storage.atomic_writes = atomic_writes;
+        self
+    }
+    /// Sets whether file operations take an advisory lock (`flock` on Unix, `LockFileEx` on Windows),
+    /// so that multiple processes using the same cache file don't clobber each other's changes
+    ///
+    /// The lock is taken on a sibling `.lock` file and only held for the duration of each operation.
+    /// Operations that only read the cache file, like [`get`](Cached::get), take it shared,
+    /// so that they only wait for saves and not for each other.
+    /// Disabled by default.
+    pub fn locking(mut self, locking: bool) -> Self {
+        self.storage.locking = locking;
+        self
+    }
+    /// Fails taking the advisory lock with [`TimedOut`](std::io::ErrorKind::TimedOut) if another process
+    /// holds it for longer than the given timeout, instead of waiting for as long as it's held
+    ///
+    /// The lock is released by the OS when the process holding it exits, even if it crashes,
+    /// so it can't be left stale; this guards against a process that hangs while holding it.
+    /// Only has an effect with [`locking`](CachedBuilder::locking).
+    pub fn lock_timeout(mut self, timeout: Duration) -> Self {
+        self.storage.lock_timeout = Some(timeout);
+        self
+    }
+    /// Sets how many backups of the previous contents of the cache file to keep
+    ///
+    /// Before each save, the current file is copied to `<path>.bak.1`,
+    /// with older backups shifted to `<path>.bak.2` and so on up to the given count.
+    /// A backup can be restored with [`Cached::restore_backup`].
+    /// No backups are kept by default.
+    pub fn backups(mut self, count: usize) -> Self {
+        self.storage.backups = count;
+        self
+    }
+    /// Sets a separate file to load from as long as the cache file doesn't exist,
+    /// such as defaults from a read-only system path
+    ///
+    /// Saves always go to the cache file, after which it is loaded instead.
+    /// When watching for changes, only the cache file is watched.
+    pub fn load_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        self.storage.load_path = Some(path.into().into_boxed_path());
+        self
+    }
+    /// Sets other files to load from whenever one of them has been modified more recently
+    /// than the cache file, such as overrides in several locations
+    ///
+    /// Saves always go to the cache file. When watching for changes, only the cache file is watched.
+    pub fn search_paths<I: IntoIterator<Item = PathBuf>>(mut self, paths: I) -> Self {
+        self.storage.search_paths = paths.into_iter().map(PathBuf::into_boxed_path).collect();
+        self
+    }
+    /// Sets the file that saves go to, keeping the current path as the file to load from
+    /// as long as that file doesn't exist, see [`load_path`](CachedBuilder::load_path)
+    pub fn save_path<P: Into<PathBuf>>(mut self, path: P) -> Self {
+        let old = mem::replace(&mut self.storage.path, path.into().into_boxed_path());
+        if self.storage.load_path.is_none() {
+            self.storage.load_path = Some(old);
+        }
+        self
+    }
+    /// Sets whether saves are synced to disk (`fsync`) before they are considered done,
+    /// so that a save that returned successfully survives a power loss
+    ///
+    /// With atomic writes, the directory of the cache file is synced as well after the rename.
+    /// This makes saves slower. Disabled by default.
+    pub fn durable(mut self, durable: bool) -> Self {
+        self.storage.durable = durable;
+        self
+    }
+    /// Sets the directory where atomic writes put the temporary file before it is renamed over the cache file
+    ///
+    /// By default it's put next to the cache file. The directory has to be on the same filesystem
+    /// as the cache file, since renaming across filesystems fails. Every save uses its own temporary file,
+    /// so cache files of the same name in different directories can share the temporary directory.
+    pub fn temp_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        self.storage.temp_dir = Some(dir.into().into_boxed_path());
+        self
+    }
+    /// Sets the permissions that the cache file is created with on Unix, such as `0o600`
+    /// for a file that only the owner can read, which is a no-op on other platforms
+    ///
+    /// The mode is set when the file is opened, so the file is never accessible with other permissions.
+    /// It is subject to the umask, like with `OpenOptionsExt::mode`.
+    /// With atomic writes, the file is recreated with the mode on every save.
+    pub fn mode(mut self, mode: u32) -> Self {
+        self.storage.mode = Some(mode);
+        self
+    }
+    /// Sets whether saves keep the permissions of the cache file, which would otherwise be reset
+    /// when an atomic write replaces the file with a new one
+    ///
+    /// On Unix, the owner and group are kept as well if the process is allowed to change them,
+    /// which usually needs root. Permissions set with [`mode`](CachedBuilder::mode) only apply
+    /// to a cache file that doesn't exist yet then. Disabled by default.
+    pub fn preserve_permissions(mut self, preserve_permissions: bool) -> Self {
+        self.storage.preserve_permissions = preserve_permissions;
+        self
+    }
+    /// Sets whether to create the missing parent directories of the cache file when saving,
+    /// instead of failing with [`NotFound`](std::io::ErrorKind::NotFound)
+    ///
+    /// This includes the temporary directory for atomic writes, if one is set.
+    /// Disabled by default.
+    pub fn create_dirs(mut self, create_dirs: bool) -> Self {
+        self.storage.create_dirs = create_dirs;
+        self
+    }
+    /// Keeps a sidecar metadata file `<path>.meta` next to the cache file, recording the given
+    /// schema version, the length and CRC-32 of the data and when it was saved, see [`Sidecar`](crate::storage::Sidecar)
+    ///
+    /// The format of the cache file itself is left untouched. When loading, data that doesn't match
+    /// its sidecar, or that was saved with a newer schema version, fails with
+    /// [`InvalidData`](std::io::ErrorKind::InvalidData), so [`load_retries`](CachedBuilder::load_retries)
+    /// and [`on_load_error`](CachedBuilder::on_load_error) apply. A file without a sidecar is loaded as it is.
+    /// Use [`FileStorage::sidecar`] to read the schema version before loading, e.g. to migrate older data.
+    ///
+    /// Since the sidecar is written after the cache file, readers briefly see a mismatch during saves.
+    pub fn sidecar(mut self, version: u32) -> Self {
+        self.storage.sidecar = Some(version);
+        self
+    }
+    /// Fails loading with [`TimedOut`](std::io::ErrorKind::TimedOut) if reading the cache file
+    /// or its metadata takes longer than the given timeout, e.g. on a network filesystem whose server hangs
+    ///
+    /// Each read is then done on a helper thread, and the file is read in full before it's parsed.
+    /// The inner value is left as it is when it times out.
+    /// This is best effort: a read that never returns can't be stopped, so its thread is leaked
+    /// and keeps the file open until it does return.
+    /// Saving isn't covered by the timeout.
+    pub fn load_timeout(mut self, timeout: Duration) -> Self {
+        self.storage.load_timeout = Some(timeout);
+        self
+    }
+    /// Sets whether a cache file with a `.gz` or `.zst` extension is compressed with gzip or zstd,
+    /// with the `gzip` or `zstd` feature
+    ///
+    /// Loading and saving then decompresses and compresses the file, so formats
+    /// see the uncompressed data, e.g. `data.json.gz` holds gzipped JSON.
+    /// Files with other extensions aren't compressed.
+    /// This should be disabled when the format already compresses, such as [`Gzip`](crate::formats::Gzip).
+    /// Enabled by default.
+    pub fn compression(mut self, compression: bool) -> Self {
+        self.storage.compression = compression;
+        self
+    }
+    #[cfg(feature = "watch")]
+    /// Sets whether to watch the directory of the cache file for changes,
+    /// instead of reading its metadata on every access to see if it has changed
+    ///
+    /// The metadata is then only read after the OS has reported a change to the file,
+    /// which saves a syscall on each access for frequently read caches.
+    /// Watching stops when the instance is dropped.
+    /// Disabled by default.
+    pub fn watch(mut self, watch: bool) -> Self {
+        self.storage.watch = watch;
+        self
+    }
+}
+
+impl<T: Cache, F: FnOnce() -> T, S: Storage> CachedBuilder<T, F, S> {
+    /// Makes a builder for an instance backed by the given storage, using the given default function
+    pub fn new(default: F, storage: S) -> Self {
+        CachedBuilder {
+            storage,
+            default,
+            options: Options::default(),
+            create_if_missing: false,
+            snapshot: None,
+            on_reload: None,
+            retention: None,
+            validator: None,
+            changed: None,
+            factory: None,
+            _marker: PhantomData,
+        }
+    }
+    /// Sets the function giving the value to use when the cache file doesn't exist
+    pub fn default_fn<G: FnOnce() -> T>(self, default: G) -> CachedBuilder<T, G, S> {
+        CachedBuilder {
+            storage: self.storage,
+            default,
+            options: self.options,
+            create_if_missing: self.create_if_missing,
+            snapshot: self.snapshot,
+            on_reload: self.on_reload,
+            retention: self.retention,
+            validator: self.validator,
+            changed: self.changed,
+            factory: self.factory,
+            _marker: PhantomData,
+        }
+    }
+    #[inline]
+    /// Sets the value to use when the cache file doesn't exist, like [`default_fn`](CachedBuilder::default_fn)
+    /// with a function giving the value
+    pub fn initial(self, value: T) -> CachedBuilder<T, impl FnOnce() -> T, S> {
+        self.default_fn(move || value)
+    }
+    /// Debounces saves after mutations, so rapid mutations are coalesced into one save
+    ///
+    /// A mutation then only schedules a save for when the given duration has passed
+    /// without further mutations. There is no background thread, so the save is done by the first
+    /// [`get`](Cached::get) or [`with_mut`](Cached::with_mut) call after that.
+    /// A pending save can also be done right away with [`save_pending`](Cached::save_pending),
+    /// and is done on drop (ignoring errors).
+    ///
+    /// The cache file is not reloaded while a save is pending.
+    pub fn debounce(mut self, debounce: Duration) -> Self {
+        self.options.debounce = Some(debounce);
+        self
+    }
+    /// Throttles saves after mutations, so that the cache file is written at most once per the given interval
+    ///
+    /// Mutations always change the inner value, but one made within the interval after the last save
+    /// only schedules a save for when the interval has passed, so intermediate values are never written.
+    /// Unlike with [`debounce`](CachedBuilder::debounce), a stream of mutations still saves once per interval.
+    /// Like a debounced save, the scheduled save is done by the first access after it's due,
+    /// right away with [`save_pending`](Cached::save_pending), or on drop, and whether one is scheduled
+    /// is told by [`pending_save`](Cached::pending_save).
+    /// This has no effect if saves are debounced.
+    pub fn max_save_rate(mut self, interval: Duration) -> Self {
+        self.options.max_save_rate = Some(interval);
+        self
+    }
+    /// Only checks whether the cache file has changed at most once per the given interval,
+    /// using the in-memory value without checking in between
+    ///
+    /// This trades staleness of up to the interval for fewer syscalls on frequently read caches.
+    /// Note that this also applies to the check before mutations,
+    /// so changes made by others within the interval can be overwritten.
+    pub fn check_interval(mut self, interval: Duration) -> Self {
+        self.options.check_interval = Some(interval);
+        self
+    }
+    /// Sets the capacity in bytes of the buffers that the stored data is loaded and saved through,
+    /// instead of the standard library's default of 8 KiB
+    ///
+    /// Larger buffers can speed up loading and saving multi-megabyte values on slow storage.
+    pub fn buffer_size(mut self, buffer_size: usize) -> Self {
+        self.options.buffer_size = Some(buffer_size);
+        self
+    }
+    /// Sets whether to reserve the space for the data before a full save, so that running out of space
+    /// fails the save before anything is written and the file is less fragmented
+    ///
+    /// The value is serialized once without writing it to find its length, which then is reserved with
+    /// `fallocate` on Linux and by setting the file's length elsewhere, where running out of space
+    /// might only be noticed while writing. Compressed cache files aren't preallocated.
+    /// Disabled by default.
+    pub fn preallocate(mut self, preallocate: bool) -> Self {
+        self.options.preallocate = preallocate;
+        self
+    }
+    /// Sets whether to tell if the cache file has changed by reading and hashing all of it on every check,
+    /// instead of comparing its modification time, length and file ID
+    ///
+    /// The value is only parsed again if the hash differs from that of what was last loaded or saved.
+    /// This is more expensive, but reliable where the metadata isn't, such as on some network filesystems.
+    /// Combine it with [`check_interval`](CachedBuilder::check_interval) to read the file less often.
+    /// With [`append`](CachedBuilder::append), only the hash of the data written in full is known,
+    /// so the file is parsed again after every append. Disabled by default.
+    pub fn hash_based_reload(mut self, hash_based_reload: bool) -> Self {
+        self.options.hash_based_reload = hash_based_reload;
+        self
+    }
+    /// Reloads the cache file when more than the given time-to-live has passed
+    /// since it was last loaded or saved, even if its metadata hasn't changed
+    ///
+    /// This bounds staleness when the modification time of the file can't be relied upon.
+    pub fn ttl(mut self, ttl: Duration) -> Self {
+        self.options.ttl = Some(ttl);
+        self
+    }
+    /// Waits for the given delay after noticing that the cache file has changed, and only loads it
+    /// once it hasn't changed again during the delay, e.g. for configuration files edited by hand
+    ///
+    /// Text editors often write a file in several steps when saving it, so loading it right away
+    /// can parse a partially written file. The delay is waited again for as long as the file keeps
+    /// changing, up to 10 times, after which it's loaded anyway, and the access waits while it settles.
+    /// If the file disappears while waiting, as when an editor replaces it, it's checked again later.
+    /// Combine it with [`load_retries`](CachedBuilder::load_retries) in case the file still turns out to be
+    /// partially written. Changes from this instance's own saves are never waited for. No delay is waited by default.
+    pub fn settle_delay(mut self, delay: Duration) -> Self {
+        self.options.settle_delay = Some(delay);
+        self
+    }
+    /// Retries loading the cache file up to the given number of times when loading fails
+    /// with [`InvalidData`](std::io::ErrorKind::InvalidData) or [`UnexpectedEof`](std::io::ErrorKind::UnexpectedEof),
+    /// as happens when reading a file while another process is writing it
+    ///
+    /// The wait before each retry increases by 10 milliseconds.
+    /// This is most useful when the file is written without atomic writes.
+    /// No retries are done by default.
+    pub fn load_retries(mut self, retries: usize) -> Self {
+        self.options.load_retries = retries;
+        self
+    }
+    /// Retries saving up to the given number of times when a save fails with
+    /// [`Interrupted`](std::io::ErrorKind::Interrupted), [`WouldBlock`](std::io::ErrorKind::WouldBlock)
+    /// or [`TimedOut`](std::io::ErrorKind::TimedOut), as can happen on network filesystems
+    ///
+    /// The wait before the first retry is `backoff`, doubling with each retry.
+    /// Other errors fail right away. Appends aren't retried, since they could be written twice.
+    /// No retries are done by default.
+    pub fn save_retries(mut self, retries: usize, backoff: Duration) -> Self {
+        self.options.save_retries = retries;
+        self.options.save_backoff = backoff;
+        self
+    }
+    /// Refuses to load a cache file larger than the given number of bytes,
+    /// failing with [`CacheError::TooLarge`](crate::CacheError::TooLarge) instead
+    ///
+    /// The length is checked before the file is opened, and reading also stops at the limit
+    /// in case the file grows while it is read. This guards against running out of memory
+    /// when the file may come from an untrusted source.
+    pub fn max_size(mut self, bytes: u64) -> Self {
+        self.options.max_size = Some(bytes);
+        self
+    }
+    /// Sets whether dropping the instance saves the inner value if it has unsaved changes,
+    /// instead of only doing a pending debounced save
+    ///
+    /// This also retries saving changes whose save failed, such as with [`MutError::Unsaved`](crate::MutError::Unsaved).
+    /// Errors can't be returned from `Drop`, so a failed save is ignored,
+    /// other than being logged with the `log` feature. Use [`Cached::flush`] to catch it.
+    pub fn flush_on_drop(mut self, flush: bool) -> Self {
+        self.options.flush_on_drop = flush;
+        self
+    }
+    /// Sets the clock used for debouncing, check intervals and time-to-live instead of the system clock
+    ///
+    /// This is for testing time-based behaviour with a [`FakeClock`](crate::FakeClock).
+    pub fn clock<C: Clock + 'static>(mut self, clock: C) -> Self {
+        self.options.clock = Some(Arc::new(clock));
+        self
+    }
+    /// Sets whether the cache file is created with the default value right away if it doesn't exist,
+    /// rather than when it's first saved
+    ///
+    /// Disabled by default.
+    pub fn create_if_missing(mut self, create_if_missing: bool) -> Self {
+        self.create_if_missing = create_if_missing;
+        self
+    }
+    /// Sets whether the instance only keeps the value in memory if the cache file can't be written,
+    /// e.g. in a sandbox, instead of failing to save
+    ///
+    /// This is checked once when the instance is made, by creating and removing a temporary file
+    /// next to the cache file. If that fails, the cache file is never loaded or saved, so the value
+    /// starts out as the default and saves do nothing. Operations on the file itself,
+    /// like [`Cached::read_raw`], still go to the file. See [`Cached::is_persistent`]. Disabled by default.
+    pub fn fallback_in_memory(mut self, fallback_in_memory: bool) -> Self {
+        self.options.fallback_in_memory = fallback_in_memory;
+        self
+    }
+    /// Sets whether saves append to the stored data using [`Cache::append`] instead of replacing it,
+    /// for append-only formats such as logs
+    ///
+    /// A clone of the value as it was last loaded or saved is kept, to be passed to `append`.
+    /// The data is only replaced using [`Cache::save`] when nothing has been loaded or saved yet.
+    /// Since appends are done in place, they aren't atomic and a failed append can leave
+    /// partially written data behind.
+    ///
+    /// The type has to implement `append`, since the default rewrites the whole value.
+    /// Disabled by default.
+    pub fn append(mut self, append: bool) -> Self
+    where T: Clone {
+        self.snapshot = if append { Some(T::clone) } else { None };
+        self
+    }
+    /// Sets whether the inner value is reset to the default when the cache file is deleted
+    /// after it was loaded or saved
+    ///
+    /// The [`on_reload`](CachedBuilder::on_reload) callback is run with the default value then.
+    /// By default, the last value keeps being used when the file disappears, until it's saved again.
+    pub fn reset_on_missing(mut self, reset_on_missing: bool) -> Self
+    where T: Default {
+        self.options.reset_on_missing = reset_on_missing;
+        if self.factory.is_none() {
+            self.factory = Some(Factory::Default(T::default));
+        }
+        self
+    }
+    /// Sets a flag that cancels saves when set, such as on shutdown
+    ///
+    /// The flag is checked on every write done by [`Cache::save`], so a save that writes
+    /// incrementally stops partway, failing with [`Interrupted`](std::io::ErrorKind::Interrupted).
+    /// With atomic writes, the stored data is then left as it was.
+    /// Saves keep failing for as long as the flag is set, and cancelled saves aren't retried.
+    pub fn cancel_token(mut self, cancel: Arc<AtomicBool>) -> Self {
+        self.options.cancel = Some(cancel);
+        self
+    }
+    /// Sets what to do when the stored data fails to load with [`CacheError::Load`](crate::CacheError::Load),
+    /// such as when it is corrupt
+    ///
+    /// Other errors, like failing to open the file, are always returned.
+    /// Defaults to [`LoadErrorPolicy::Propagate`].
+    pub fn on_load_error(mut self, policy: LoadErrorPolicy) -> Self
+    where T: Default {
+        self.options.load_error_policy = policy;
+        if self.factory.is_none() {
+            self.factory = Some(Factory::Default(T::default));
+        }
+        self
+    }
+    /// Sets whether an empty cache file gives the default value instead of being loaded
+    ///
+    /// This recovers from a file that was left empty by a crash in the middle of a save without
+    /// atomic writes, which most formats fail to load. The file is replaced on the next save.
+    pub fn treat_empty_as_default(mut self, empty_as_default: bool) -> Self
+    where T: Default {
+        self.options.empty_as_default = empty_as_default;
+        if self.factory.is_none() {
+            self.factory = Some(Factory::Default(T::default));
+        }
+        self
+    }
+    /// Sets a function giving the default value, which unlike the default function given to the builder
+    /// is kept, so that it can give the default again when resetting
+    ///
+    /// It's used instead of the builder's default function, and instead of the type's `Default` implementation
+    /// for [`reset_on_missing`](CachedBuilder::reset_on_missing),
+    /// [`treat_empty_as_default`](CachedBuilder::treat_empty_as_default) and [`Cached::clear`].
+    pub fn default_factory<G: Fn() -> T + Send + 'static>(mut self, factory: G) -> Self {
+        self.factory = Some(Factory::Custom(Box::new(factory)));
+        self
+    }
+    /// Sets a callback that is run whenever the cache file has been loaded,
+    /// such as after it was changed by someone else, but not after saves
+    ///
+    /// The callback runs while the inner value is borrowed,
+    /// so it can read the value but mustn't mutate the instance.
+    pub fn on_reload<G: Fn(&T) + Send + 'static>(mut self, callback: G) -> Self {
+        self.on_reload = Some(ReloadHook(Box::new(callback)));
+        self
+    }
+    /// Sets a callback that trims the inner value right before every save, e.g. dropping entries
+    /// that are too old or too many, so that the cache file doesn't grow without bound
+    ///
+    /// What to drop is up to the callback. The trimmed value is what stays in memory as well.
+    /// With [`append`](CachedBuilder::append), dropped entries are only removed from the cache file
+    /// once it is rewritten in full.
+    pub fn retention<G: Fn(&mut T) + Send + 'static>(mut self, trim: G) -> Self {
+        self.retention = Some(RetentionHook(Box::new(trim)));
+        self
+    }
+    /// Sets a callback that checks the inner value right before every save, e.g. that invariants hold,
+    /// failing the save with [`CacheError::Invalid`](crate::CacheError::Invalid) if it returns an error
+    ///
+    /// The stored data is then left as it was. After a mutation like [`with_mut`](Cached::with_mut),
+    /// the error is given in [`MutError::Unsaved`](crate::MutError::Unsaved) and the invalid value
+    /// stays in memory, so it should be rolled back, e.g. with [`reload`](Cached::reload).
+    /// It runs after the [`retention`](CachedBuilder::retention) callback.
+    pub fn validate<G: Fn(&T) -> io::Result<()> + Send + 'static>(mut self, validator: G) -> Self {
+        self.validator = Some(Validator(Box::new(validator)));
+        self
+    }
+    /// Sets a function deciding whether the inner value has changed from the one last loaded or saved,
+    /// which is given first, instead of comparing their serialized forms, e.g. for ignoring timestamps
+    ///
+    /// Saving after a mutation or with [`save_if_dirty`](Cached::save_if_dirty) is skipped if it returns `false`,
+    /// and it's what [`is_dirty`](Cached::is_dirty) tells. A clone of the value last loaded or saved
+    /// is kept for it.
+    pub fn changed_predicate<G: Fn(&T, &T) -> bool + Send + 'static>(mut self, changed: G) -> Self
+    where T: Clone {
+        self.changed = Some(ChangedPredicate { changed: Box::new(changed), clone: T::clone });
+        self
+    }
+    /// Makes the instance, loading the cache file if it exists
+    pub fn build(self) -> Result<Cached<T, S>> {
+        let create_if_missing = self.create_if_missing;
+        let ret = self.build_lazy();
+        ret.check_saves_replace()?;
+        ret.check_load()?;
+
+        if create_if_missing && persist::stat(&ret.storage)?.is_none() {
+            ret.save()?;
+        }
+        Ok(ret)
+    }
+    /// Makes the instance without touching the cache file,
+    /// so that it's only loaded on first access
+    ///
+    /// [`create_if_missing`](CachedBuilder::create_if_missing) has no effect then.
+    pub fn build_lazy(mut self) -> Cached<T, S> {
+        if self.options.fallback_in_memory {
+            if let Err(_e) = self.storage.check_writable() {
+                debug!("keeping the value in memory, since saving would fail: {}", _e);
+                self.options.in_memory = true;
+            }
+        }
+        let inner = match &self.factory {
+            Some(factory @ Factory::Custom(_)) => factory.make(),
+            _ => (self.default)(),
+        };
+        Cached {
+            last_modified: RefCell::new(SystemTime::UNIX_EPOCH),
+            last_len: RefCell::new(0),
+            last_file_id: Cell::new(None),
+            content_hash: Cell::new(None),
+            save_deadline: Cell::new(None),
+            last_checked: Cell::new(None),
+            last_written: Cell::new(None),
+            last_synced: Cell::new(None),
+            stats: Cell::new(Default::default()),
+            untouched: Cell::new(true),
+            autosave_paused: Cell::new(false),
+            snapshot: self.snapshot,
+            last_saved: RefCell::new(None),
+            baseline: RefCell::new(None),
+            on_reload: self.on_reload,
+            retention: self.retention,
+            validator: self.validator,
+            changed: self.changed,
+            subscribers: RefCell::new(Vec::new()),
+            factory: self.factory,
+            codec: None,
+            options: self.options,
+            storage: self.storage,
+            inner: Some(RefCell::new(inner)),
+        }
+    }
+    /// Makes a read-only instance, loading the cache file if it exists
+    ///
+    /// Options only affecting saving, such as [`create_if_missing`](CachedBuilder::create_if_missing),
+    /// have no effect.
+    pub fn build_read_only(mut self) -> Result<ReadOnlyCached<T, S>> {
+        self.create_if_missing = false;
+        self.options.read_only = true;
+        self.build().map(ReadOnlyCached::from_cached)
+    }
+}
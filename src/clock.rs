@@ -0,0 +1,58 @@
+//! Sources of the current time, so that time-based behaviour can be tested without sleeping
+
+use std::{
+    fmt::Debug,
+    sync::{Arc, Mutex},
+    time::{Duration, Instant},
+};
+
+/// Gives the current time for debouncing, check intervals and time-to-live,
+/// see [`CachedBuilder::clock`](crate::CachedBuilder::clock)
+///
+/// Modification times still come from the storage.
+pub trait Clock: Debug + Send + Sync {
+    /// The current time
+    fn now(&self) -> Instant;
+}
+
+/// The system's monotonic clock, which is used by default
+#[derive(Debug, Default, Clone, Copy)]
+pub struct SystemClock;
+
+impl Clock for SystemClock {
+    #[inline]
+    fn now(&self) -> Instant {
+        Instant::now()
+    }
+}
+
+/// A clock that only moves forward when told to, for tests
+///
+/// Clones share the same time, so a clone can be kept to advance the clock
+/// given to the builder.
+#[derive(Debug, Clone)]
+pub struct FakeClock(Arc<Mutex<Instant>>);
+
+impl FakeClock {
+    /// Makes a clock starting at the current time
+    pub fn new() -> Self {
+        FakeClock(Arc::new(Mutex::new(Instant::now())))
+    }
+    /// Moves the clock forward by the given duration
+    pub fn advance(&self, duration: Duration) {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner()) += duration;
+    }
+}
+
+impl Default for FakeClock {
+    #[inline]
+    fn default() -> Self {
+        FakeClock::new()
+    }
+}
+
+impl Clock for FakeClock {
+    fn now(&self) -> Instant {
+        *self.0.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
@@ -0,0 +1,29 @@
+use crate::Cache;
+
+/// What a mutation changed in the inner value, see [`Cached::do_mut_diff`](crate::Cached::do_mut_diff)
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Diff {
+    changed: bool,
+    keys: Vec<String>,
+}
+
+impl Diff {
+    /// Compares the value from before a mutation with the mutated one
+    pub(crate) fn between<T: Cache + PartialEq>(old: &T, new: &T) -> Self {
+        let changed = old != new;
+        let keys = if changed { new.diff(old) } else { Vec::new() };
+        Diff { changed, keys }
+    }
+    #[inline]
+    /// Whether the value was changed
+    pub fn is_changed(&self) -> bool {
+        self.changed
+    }
+    #[inline]
+    /// The keys that were changed, as given by [`Cache::diff`]
+    ///
+    /// This is empty if the type doesn't tell what changed, even if it was changed.
+    pub fn keys(&self) -> &[String] {
+        &self.keys
+    }
+}
@@ -0,0 +1,63 @@
+use std::{
+    cell::RefMut,
+    ops::{Deref, DerefMut},
+    thread,
+};
+
+use crate::{Cache, Cached, Result, storage::{FileStorage, Storage}};
+
+/// A guard giving mutable access to the inner value of a [`Cached`],
+/// saving it once when the guard is committed or dropped
+/// (if the value was changed, see [`Cached::save_if_dirty`])
+///
+/// Created by [`Cached::edit`].
+///
+/// Prefer calling [`commit`](EditGuard::commit) so that a failed save can be handled.
+/// Dropping the guard without committing still saves,
+/// but since `Drop` cannot return an error, a failed save will panic.
+/// Nothing is saved when the guard is dropped while the thread is panicking,
+/// so that a half-done edit isn't written, though it stays in memory.
+#[must_use = "the value is saved when the guard is committed or dropped"]
+pub struct EditGuard<'a, T: Cache, S: Storage = FileStorage> {
+    cached: &'a Cached<T, S>,
+    inner: Option<RefMut<'a, T>>,
+}
+
+impl<'a, T: Cache, S: Storage> EditGuard<'a, T, S> {
+    pub(crate) fn new(cached: &'a Cached<T, S>, inner: RefMut<'a, T>) -> Self {
+        EditGuard {
+            cached,
+            inner: Some(inner),
+        }
+    }
+    /// Releases the mutable borrow and saves the inner value to the cache file if it was changed
+    pub fn commit(mut self) -> Result<()> {
+        self.inner = None;
+        self.cached.save_if_dirty().map(|_| ())
+    }
+}
+
+impl<T: Cache, S: Storage> Deref for EditGuard<'_, T, S> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        self.inner.as_ref().expect("guard has been committed")
+    }
+}
+
+impl<T: Cache, S: Storage> DerefMut for EditGuard<'_, T, S> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        self.inner.as_mut().expect("guard has been committed")
+    }
+}
+
+impl<T: Cache, S: Storage> Drop for EditGuard<'_, T, S> {
+    fn drop(&mut self) {
+        if self.inner.take().is_some() && !thread::panicking() {
+            if let Err(e) = self.cached.save_if_dirty() {
+                panic!("failed to save cache: {}", e);
+            }
+        }
+    }
+}
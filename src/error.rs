@@ -0,0 +1,204 @@
+use std::{
+    io,
+    fmt::{self, Display},
+    error::Error,
+    path::{Path, PathBuf},
+    result::Result as StdResult,
+};
+
+/// Result type with [`CacheError`] as the default error
+pub type Result<T, E = CacheError> = StdResult<T, E>;
+
+/// Error from an operation on a cache, telling which step failed
+#[derive(Debug)]
+#[non_exhaustive]
+pub enum CacheError {
+    /// Reading the metadata of the cache file failed
+    ///
+    /// With watching enabled, this includes failing to start watching the file.
+    Metadata(io::Error),
+    /// Opening the cache file for reading failed
+    Open(io::Error),
+    /// Loading the inner value from the cache file failed (see [`Cache::load`](crate::Cache::load))
+    Load(io::Error),
+    /// Saving the inner value to the cache file failed
+    Save(io::Error),
+    /// Serializing the inner value failed partway, after the given number of bytes had been written
+    ///
+    /// With atomic writes, the cache file is left as it was.
+    Write {
+        /// The error from [`Cache::save`](crate::Cache::save)
+        source: io::Error,
+        /// How many bytes were written before the error
+        bytes_written: u64,
+    },
+    /// Taking the advisory lock on the cache file failed
+    Lock(io::Error),
+    /// A thread panicked while holding a lock on the inner value
+    Poisoned,
+    /// The inner value is already borrowed in a way that conflicts with the operation,
+    /// such as by an [`EditGuard`](crate::EditGuard) or a reference from [`Cached::get`](crate::Cached::get)
+    Busy,
+    /// The stored data is larger than the maximum size in bytes,
+    /// see [`CachedBuilder::max_size`](crate::CachedBuilder::max_size)
+    TooLarge(u64),
+    /// The inner value was rejected by the validator before saving it,
+    /// see [`CachedBuilder::validate`](crate::CachedBuilder::validate)
+    Invalid(io::Error),
+}
+
+impl CacheError {
+    /// The underlying I/O error, if any
+    pub fn io_error(&self) -> Option<&io::Error> {
+        match self {
+            CacheError::Metadata(e)
+            | CacheError::Open(e)
+            | CacheError::Load(e)
+            | CacheError::Save(e)
+            | CacheError::Write { source: e, .. }
+            | CacheError::Lock(e)
+            | CacheError::Invalid(e) => Some(e),
+            CacheError::Poisoned | CacheError::Busy | CacheError::TooLarge(_) => None,
+        }
+    }
+    /// The kind of the underlying I/O error, which is `WouldBlock` for [`Busy`](CacheError::Busy)
+    /// and `InvalidData` for [`TooLarge`](CacheError::TooLarge)
+    pub fn kind(&self) -> io::ErrorKind {
+        match self {
+            CacheError::Busy => io::ErrorKind::WouldBlock,
+            CacheError::TooLarge(_) => io::ErrorKind::InvalidData,
+            _ => self.io_error().map_or(io::ErrorKind::Other, io::Error::kind),
+        }
+    }
+}
+
+impl CacheError {
+    /// The path of the file the error happened to, if known
+    pub fn path(&self) -> Option<&Path> {
+        let e = self.io_error()?.get_ref()?.downcast_ref::<PathError>()?;
+        Some(&e.path)
+    }
+}
+
+/// I/O error with the path of the file it happened to, so that messages tell which cache file failed
+#[derive(Debug)]
+struct PathError {
+    path: PathBuf,
+    source: io::Error,
+}
+
+impl Display for PathError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.source)
+    }
+}
+
+impl Error for PathError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Adds the path to an I/O error, keeping its kind
+pub(crate) fn with_path(path: &Path) -> impl FnOnce(io::Error) -> io::Error + '_ {
+    move |source| io::Error::new(source.kind(), PathError { path: path.to_owned(), source })
+}
+
+impl Display for CacheError {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            CacheError::Metadata(e) => write!(f, "could not read metadata of cache file: {}", e),
+            CacheError::Open(e) => write!(f, "could not open cache file: {}", e),
+            CacheError::Load(e) => write!(f, "could not load cache file: {}", e),
+            CacheError::Save(e) => write!(f, "could not save cache file: {}", e),
+            CacheError::Write { source, bytes_written } => {
+                write!(f, "could not save cache file after writing {} bytes: {}", bytes_written, source)
+            }
+            CacheError::Lock(e) => write!(f, "could not lock cache file: {}", e),
+            CacheError::Poisoned => write!(f, "cache lock poisoned by a panicking thread"),
+            CacheError::Busy => write!(f, "cached value is already borrowed"),
+            CacheError::TooLarge(max) => write!(f, "cache file is larger than the maximum of {} bytes", max),
+            CacheError::Invalid(e) => write!(f, "cached value is invalid: {}", e),
+        }
+    }
+}
+
+impl Error for CacheError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        self.io_error().map(|e| e as _)
+    }
+}
+
+impl From<CacheError> for io::Error {
+    /// Wraps the error, keeping the kind of the underlying I/O error
+    fn from(e: CacheError) -> Self {
+        io::Error::new(e.kind(), e)
+    }
+}
+
+/// Error from mutating the inner value, which keeps the result of the closure if it was run
+#[derive(Debug)]
+pub enum MutError<R> {
+    /// The closure wasn't run, since bringing the inner value up to date with the cache file failed
+    NotRun(CacheError),
+    /// The closure was run, so the inner value has been mutated, but saving it afterwards failed
+    Unsaved(R, CacheError),
+}
+
+impl<R> MutError<R> {
+    /// The result of the closure, if it was run
+    pub fn into_value(self) -> Option<R> {
+        match self {
+            MutError::NotRun(_) => None,
+            MutError::Unsaved(r, _) => Some(r),
+        }
+    }
+    /// The underlying error
+    pub fn error(&self) -> &CacheError {
+        match self {
+            MutError::NotRun(e) | MutError::Unsaved(_, e) => e,
+        }
+    }
+    /// The result of the closure, if it was run, together with the underlying error
+    pub fn into_parts(self) -> (Option<R>, CacheError) {
+        match self {
+            MutError::NotRun(e) => (None, e),
+            MutError::Unsaved(r, e) => (Some(r), e),
+        }
+    }
+    /// Discards the closure's result and gives the underlying error
+    pub fn into_error(self) -> CacheError {
+        match self {
+            MutError::NotRun(e) | MutError::Unsaved(_, e) => e,
+        }
+    }
+}
+
+impl<R> Display for MutError<R> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            MutError::NotRun(e) => write!(f, "mutation not run: {}", e),
+            MutError::Unsaved(_, e) => write!(f, "mutation not saved: {}", e),
+        }
+    }
+}
+
+impl<R: fmt::Debug> Error for MutError<R> {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        Some(self.error())
+    }
+}
+
+impl<R> From<MutError<R>> for CacheError {
+    #[inline]
+    fn from(e: MutError<R>) -> Self {
+        e.into_error()
+    }
+}
+
+impl<R> From<MutError<R>> for io::Error {
+    #[inline]
+    fn from(e: MutError<R>) -> Self {
+        e.into_error().into()
+    }
+}
@@ -0,0 +1,47 @@
+use std::{
+    io::{self, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::Cache;
+
+/// Wrapper that caches any serde type in the compact binary format of bincode
+///
+/// For example, `Cached<Bincode<Vec<f64>>>` stores the numbers without formatting them as text.
+/// Loading deserializes straight from the reader, so the file isn't read into memory first.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Bincode<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned> Cache for Bincode<T> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        bincode::serialize_into(writer, &self.0).map_err(|e| into_io(*e))
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        bincode::deserialize_from(reader).map(Bincode).map_err(|e| into_io(*e))
+    }
+}
+
+/// Unwraps I/O errors, and treats the rest as invalid data
+fn into_io(e: bincode::ErrorKind) -> io::Error {
+    match e {
+        bincode::ErrorKind::Io(e) => e,
+        e => io::Error::new(io::ErrorKind::InvalidData, e),
+    }
+}
+
+impl<T> Deref for Bincode<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Bincode<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
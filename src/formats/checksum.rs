@@ -0,0 +1,87 @@
+use std::{
+    io::{Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use crate::{Cache, hashing::crc32};
+
+/// Size in bytes of the header written by [`Checksummed`]
+pub const CHECKSUM_HEADER_LEN: usize = 12;
+
+/// Adapter that verifies the data of another [`Cache`] implementor with a checksum,
+/// so corrupted files fail to load instead of loading garbage
+///
+/// The data is prefixed by a header of [`CHECKSUM_HEADER_LEN`] bytes:
+/// the length of the inner data as a little-endian `u64`,
+/// followed by its CRC-32 (IEEE) as a little-endian `u32`.
+/// Loading fails with [`ErrorKind::InvalidData`] if the length or checksum doesn't match.
+///
+/// Since the header comes first, the inner data is buffered in memory when saving.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Checksummed<C>(pub C);
+
+impl<C: Cache> Cache for Checksummed<C> {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut payload = Vec::new();
+        self.0.save(&mut payload)?;
+
+        writer.write_all(&(payload.len() as u64).to_le_bytes())?;
+        writer.write_all(&crc32(&payload).to_le_bytes())?;
+        writer.write_all(&payload)
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut header = [0; CHECKSUM_HEADER_LEN];
+        reader.read_exact(&mut header).map_err(invalid_if_eof)?;
+        let mut len = [0; 8];
+        let mut checksum = [0; 4];
+        len.copy_from_slice(&header[..8]);
+        checksum.copy_from_slice(&header[8..]);
+        let len = u64::from_le_bytes(len);
+
+        let mut payload = Vec::new();
+        reader.take(len).read_to_end(&mut payload)?;
+        if payload.len() as u64 != len {
+            return Err(Error::new(ErrorKind::InvalidData, "data is shorter than its recorded length"));
+        }
+        if crc32(&payload) != u32::from_le_bytes(checksum) {
+            return Err(Error::new(ErrorKind::InvalidData, "checksum mismatch"));
+        }
+
+        C::load(&payload[..]).map(Checksummed)
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.0.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.0.merge(disk.0)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.0.diff(&old.0)
+    }
+    fn estimated_size(&self) -> usize {
+        self.0.estimated_size()
+    }
+}
+
+fn invalid_if_eof(e: Error) -> Error {
+    if e.kind() == ErrorKind::UnexpectedEof {
+        Error::new(ErrorKind::InvalidData, "checksum header is truncated")
+    } else {
+        e
+    }
+}
+
+impl<C> Deref for Checksummed<C> {
+    type Target = C;
+    #[inline]
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C> DerefMut for Checksummed<C> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+}
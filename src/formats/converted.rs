@@ -0,0 +1,107 @@
+use std::{
+    io::{Read, Write, Result},
+    ops::{Deref, DerefMut},
+    marker::PhantomData,
+    fmt::{self, Debug},
+};
+
+use crate::Cache;
+
+/// Adapter that stores a value of type `L` in the form of another [`Cache`] implementor `S`,
+/// converting between them with `From` when saving and loading
+///
+/// This keeps the representation in the file separate from the type the program works with.
+/// For example, `Cached<Converted<Json<Vec<(String, u32)>>, Registry>>` stores a `Registry`
+/// as a JSON list of pairs, given `Registry: From<Json<Vec<(String, u32)>>>`
+/// and `Json<Vec<(String, u32)>>: From<&Registry>`.
+///
+/// The hooks of `S`, such as [`Cache::merge`], are used by converting the values to `S` and back,
+/// and a loaded value needs rewriting if the `S` it was converted from does.
+pub struct Converted<S, L> {
+    inner: L,
+    /// Whether the stored form it was loaded from needs rewriting, see [`Cache::needs_rewrite`]
+    rewrite: bool,
+    _stored: PhantomData<fn() -> S>,
+}
+
+impl<S, L> Converted<S, L> {
+    #[inline]
+    /// Wraps the value
+    pub fn new(inner: L) -> Self {
+        Converted {
+            inner,
+            rewrite: false,
+            _stored: PhantomData,
+        }
+    }
+    #[inline]
+    /// Unwraps the value
+    pub fn into_inner(self) -> L {
+        self.inner
+    }
+}
+
+impl<S, L> Cache for Converted<S, L>
+where
+    S: Cache + for<'a> From<&'a L>,
+    L: From<S>,
+{
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        S::from(&self.inner).save(writer)
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        let stored = S::load(reader)?;
+        let rewrite = stored.needs_rewrite();
+        Ok(Converted { rewrite, ..Converted::new(L::from(stored)) })
+    }
+    #[inline]
+    fn needs_rewrite(&self) -> bool {
+        self.rewrite
+    }
+    fn merge(&mut self, disk: Self) {
+        let mut merged = S::from(&self.inner);
+        merged.merge(S::from(&disk.inner));
+        self.inner = L::from(merged);
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        S::from(&self.inner).diff(&S::from(&old.inner))
+    }
+    fn estimated_size(&self) -> usize {
+        S::from(&self.inner).estimated_size()
+    }
+}
+
+impl<S, L: Default> Default for Converted<S, L> {
+    #[inline]
+    fn default() -> Self {
+        Converted::new(L::default())
+    }
+}
+
+impl<S, L: Clone> Clone for Converted<S, L> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Converted { rewrite: self.rewrite, ..Converted::new(self.inner.clone()) }
+    }
+}
+
+impl<S, L: Debug> Debug for Converted<S, L> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Converted").field(&self.inner).finish()
+    }
+}
+
+impl<S, L> Deref for Converted<S, L> {
+    type Target = L;
+    #[inline]
+    fn deref(&self) -> &L {
+        &self.inner
+    }
+}
+
+impl<S, L> DerefMut for Converted<S, L> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut L {
+        &mut self.inner
+    }
+}
@@ -0,0 +1,42 @@
+use std::{
+    io::{Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use crate::Cache;
+
+/// Wrapper for a value that is saved and loaded by the closures given to
+/// [`Cached::with_codec`](crate::Cached::with_codec) instead of a [`Cache`] implementation
+///
+/// The closures are kept by the `Cached`, so the `Cache` implementation of the wrapper itself
+/// always fails with [`ErrorKind::Unsupported`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Custom<T>(pub T);
+
+impl<T> Cache for Custom<T> {
+    fn save<W: Write>(&self, _writer: W) -> Result<()> {
+        Err(no_codec())
+    }
+    fn load<R: Read>(_reader: R) -> Result<Self> {
+        Err(no_codec())
+    }
+}
+
+fn no_codec() -> Error {
+    Error::new(ErrorKind::Unsupported, "no codec was given for the custom value")
+}
+
+impl<T> Deref for Custom<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Custom<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,134 @@
+use std::{
+    io::{Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+    marker::PhantomData,
+    fmt::{self, Debug},
+    convert::TryFrom,
+};
+
+use chacha20poly1305::{
+    ChaCha20Poly1305, Key, Nonce,
+    aead::{Aead, Generate, KeyInit},
+};
+
+use crate::Cache;
+
+/// Length in bytes of the nonce prefixed to the data by [`Encrypted`]
+const NONCE_LEN: usize = 12;
+
+/// Provides the key used by [`Encrypted`]
+///
+/// Since [`Cache::load`] only gets a reader, the key is provided by a type rather than a value.
+/// A key that is only known at runtime can be kept in a static, e.g. a `OnceLock`, set before the cache is used.
+pub trait EncryptionKey {
+    /// The 32-byte ChaCha20-Poly1305 key
+    fn key() -> [u8; 32];
+}
+
+/// Adapter that encrypts the data of another [`Cache`] implementor with ChaCha20-Poly1305,
+/// using the key provided by `K`
+///
+/// Each save uses a new random nonce, which is written before the ciphertext.
+/// Loading with the wrong key or from a tampered file fails with [`ErrorKind::InvalidData`].
+///
+/// The data is buffered in memory when saving and loading.
+pub struct Encrypted<C, K> {
+    inner: C,
+    _key: PhantomData<fn() -> K>,
+}
+
+impl<C, K> Encrypted<C, K> {
+    #[inline]
+    /// Wraps the value
+    pub fn new(inner: C) -> Self {
+        Encrypted {
+            inner,
+            _key: PhantomData,
+        }
+    }
+    #[inline]
+    /// Unwraps the value
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+}
+
+impl<C: Cache, K: EncryptionKey> Cache for Encrypted<C, K> {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        let mut plaintext = Vec::new();
+        self.inner.save(&mut plaintext)?;
+
+        let nonce = Nonce::generate();
+        let ciphertext = cipher::<K>()
+            .encrypt(&nonce, &plaintext[..])
+            .map_err(|_| Error::other("encryption failed"))?;
+
+        writer.write_all(&nonce)?;
+        writer.write_all(&ciphertext)
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        if data.len() < NONCE_LEN {
+            return Err(Error::new(ErrorKind::InvalidData, "encrypted data is truncated"));
+        }
+        let (nonce, ciphertext) = data.split_at(NONCE_LEN);
+        let nonce = Nonce::try_from(nonce).expect("nonce has the right length");
+
+        let plaintext = cipher::<K>()
+            .decrypt(&nonce, ciphertext)
+            .map_err(|_| Error::new(ErrorKind::InvalidData, "decryption failed, wrong key or tampered data"))?;
+        C::load(&plaintext[..]).map(Encrypted::new)
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.inner.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.inner.merge(disk.inner)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.inner.diff(&old.inner)
+    }
+    fn estimated_size(&self) -> usize {
+        self.inner.estimated_size()
+    }
+}
+
+fn cipher<K: EncryptionKey>() -> ChaCha20Poly1305 {
+    ChaCha20Poly1305::new(&Key::from(K::key()))
+}
+
+impl<C: Default, K> Default for Encrypted<C, K> {
+    #[inline]
+    fn default() -> Self {
+        Encrypted::new(C::default())
+    }
+}
+
+impl<C: Clone, K> Clone for Encrypted<C, K> {
+    #[inline]
+    fn clone(&self) -> Self {
+        Encrypted::new(self.inner.clone())
+    }
+}
+
+impl<C: Debug, K> Debug for Encrypted<C, K> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_tuple("Encrypted").field(&self.inner).finish()
+    }
+}
+
+impl<C, K> Deref for Encrypted<C, K> {
+    type Target = C;
+    #[inline]
+    fn deref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C, K> DerefMut for Encrypted<C, K> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
@@ -0,0 +1,117 @@
+use std::{
+    io::{Cursor, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use flate2::{Compression, read::GzDecoder, write::GzEncoder};
+
+use crate::Cache;
+
+/// Adapter that gzip-compresses the data of another [`Cache`] implementor
+///
+/// `LEVEL` is the compression level from 0 (none) to 9 (best), which defaults to 6.
+/// For example, `Cached<Gzip<Json<T>>>` stores compressed JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Gzip<C, const LEVEL: u32 = 6>(pub C);
+
+impl<C: Cache, const LEVEL: u32> Cache for Gzip<C, LEVEL> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, Compression::new(LEVEL));
+        self.0.save(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        C::load(GzDecoder::new(reader)).map(Gzip)
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.0.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.0.merge(disk.0)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.0.diff(&old.0)
+    }
+    fn estimated_size(&self) -> usize {
+        self.0.estimated_size()
+    }
+}
+
+impl<C, const LEVEL: u32> Deref for Gzip<C, LEVEL> {
+    type Target = C;
+    #[inline]
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C, const LEVEL: u32> DerefMut for Gzip<C, LEVEL> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+}
+
+/// The bytes every gzip stream starts with
+const GZIP_MAGIC: [u8; 2] = [0x1f, 0x8b];
+
+/// Like [`Gzip`], but loads data that isn't gzip-compressed as well, telling them apart by the magic bytes
+///
+/// Saving always compresses with the compression level `LEVEL`, so files that aren't compressed
+/// are compressed the next time they are saved.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct AutoDecompress<C, const LEVEL: u32 = 6>(pub C);
+
+impl<C: Cache, const LEVEL: u32> Cache for AutoDecompress<C, LEVEL> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        let mut encoder = GzEncoder::new(writer, Compression::new(LEVEL));
+        self.0.save(&mut encoder)?;
+        encoder.finish()?;
+        Ok(())
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut magic = [0; 2];
+        let mut len = 0;
+        while len < magic.len() {
+            match reader.read(&mut magic[len..])? {
+                0 => break,
+                n => len += n,
+            }
+        }
+        // The peeked bytes are put back in front of the rest for the inner loader
+        let reader = Cursor::new(&magic[..len]).chain(reader);
+        if magic[..len] == GZIP_MAGIC {
+            C::load(GzDecoder::new(reader)).map(AutoDecompress)
+        } else {
+            C::load(reader).map(AutoDecompress)
+        }
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.0.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.0.merge(disk.0)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.0.diff(&old.0)
+    }
+    fn estimated_size(&self) -> usize {
+        self.0.estimated_size()
+    }
+}
+
+impl<C, const LEVEL: u32> Deref for AutoDecompress<C, LEVEL> {
+    type Target = C;
+    #[inline]
+    fn deref(&self) -> &C {
+        &self.0
+    }
+}
+
+impl<C, const LEVEL: u32> DerefMut for AutoDecompress<C, LEVEL> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.0
+    }
+}
@@ -0,0 +1,78 @@
+use std::{
+    io::{BufRead, BufReader, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::Cache;
+
+/// Wrapper that caches any serde type as JSON
+///
+/// For example, `Cached<Json<Config>>` stores a `Config` in a JSON file.
+/// The JSON is compact, see [`PrettyJson`] for indented JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Json<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned> Cache for Json<T> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer(writer, &self.0)?;
+        Ok(())
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(reader: R) -> Result<Self> {
+        Ok(Json(serde_json::from_reader(reader)?))
+    }
+}
+
+impl<T> Deref for Json<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Json<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
+
+/// Like [`Json`], but saves the JSON indented so that it's easier to read, at the cost of size
+///
+/// Both compact and indented JSON can be loaded by either, so a cache file can be
+/// switched between them by changing the type.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct PrettyJson<T>(pub T);
+
+impl<T: Serialize + DeserializeOwned> Cache for PrettyJson<T> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        serde_json::to_writer_pretty(writer, &self.0)?;
+        Ok(())
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(reader: R) -> Result<Self> {
+        Ok(PrettyJson(serde_json::from_reader(reader)?))
+    }
+}
+
+impl<T> Deref for PrettyJson<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for PrettyJson<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,83 @@
+use std::{
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Write, Result},
+    iter::FromIterator,
+    ops::{Deref, DerefMut},
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::{Cache, StreamingCache};
+
+/// Wrapper that caches a collection of serde types as JSON lines, with one element per line
+///
+/// For example, `Cached<Jsonl<Vec<Entry>>>` stores each `Entry` as a JSON object on its own line,
+/// and a `HashMap` is stored with each entry as a `[key, value]` array.
+/// Blank lines are skipped when loading, and the elements can be visited one at a time
+/// without loading all of them, see [`StreamingCache`].
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Jsonl<T>(pub T);
+
+impl<T> Cache for Jsonl<T>
+where
+    T: IntoIterator + FromIterator<T::Item>,
+    T::Item: DeserializeOwned,
+    for<'a> &'a T: IntoIterator,
+    for<'a> <&'a T as IntoIterator>::Item: Serialize,
+{
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        for item in &self.0 {
+            serde_json::to_writer(&mut writer, &item)?;
+            writer.write_all(b"\n")?;
+        }
+        Ok(())
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(reader: R) -> Result<Self> {
+        let mut items = Vec::new();
+        Self::load_streaming(reader, |item| items.push(item))?;
+        Ok(Jsonl(items.into_iter().collect()))
+    }
+}
+
+impl<T> StreamingCache for Jsonl<T>
+where
+    T: IntoIterator + FromIterator<T::Item>,
+    T::Item: DeserializeOwned,
+    for<'a> &'a T: IntoIterator,
+    for<'a> <&'a T as IntoIterator>::Item: Serialize,
+{
+    type Record = T::Item;
+    #[inline]
+    fn into_records(self) -> impl Iterator<Item = T::Item> {
+        self.0.into_iter()
+    }
+    fn load_streaming<R: BufRead, F: FnMut(T::Item)>(reader: R, mut visitor: F) -> Result<()> {
+        for (i, line) in reader.lines().enumerate() {
+            let line = line?;
+            if line.trim().is_empty() {
+                continue;
+            }
+            let item = serde_json::from_str(&line)
+                .map_err(|e| Error::new(ErrorKind::InvalidData, format!("line {}: {}", i + 1, e)))?;
+            visitor(item);
+        }
+        Ok(())
+    }
+}
+
+impl<T> Deref for Jsonl<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Jsonl<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,102 @@
+use std::{
+    collections::{BTreeMap, HashMap},
+    hash::BuildHasher,
+    io::{BufRead, BufReader, Read, Write, Result},
+    iter::FromIterator,
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use super::multi::Format;
+use crate::Cache;
+
+/// Caches a map of serde types in the format given by the extension of the file, like [`MultiFormat`](super::MultiFormat)
+///
+/// For example, `Cached<Map<String, i64>>` stores balances by name in `accounts.json`.
+/// The entries are kept in a [`BTreeMap`], so they're always saved ordered by key
+/// and saving the same entries gives the same file.
+#[derive(Debug, Clone, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Map<K, V>(pub BTreeMap<K, V>);
+
+impl<K, V> Default for Map<K, V> {
+    #[inline]
+    fn default() -> Self {
+        Map(BTreeMap::new())
+    }
+}
+
+impl<K, V> Cache for Map<K, V>
+where
+    K: Ord + Serialize + DeserializeOwned,
+    V: Serialize + DeserializeOwned,
+{
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        Format::Json.save(&self.0, writer)
+    }
+    fn save_to<W: Write>(&self, writer: W, path: &Path) -> Result<()> {
+        Format::from_path(path)?.save(&self.0, writer)
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(reader: R) -> Result<Self> {
+        Format::Json.load(reader).map(Map)
+    }
+    fn load_from<R: BufRead>(reader: R, path: &Path) -> Result<Self> {
+        Format::from_path(path)?.load(reader).map(Map)
+    }
+}
+
+impl<K, V> Deref for Map<K, V> {
+    type Target = BTreeMap<K, V>;
+    #[inline]
+    fn deref(&self) -> &BTreeMap<K, V> {
+        &self.0
+    }
+}
+
+impl<K, V> DerefMut for Map<K, V> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut BTreeMap<K, V> {
+        &mut self.0
+    }
+}
+
+impl<K, V> From<BTreeMap<K, V>> for Map<K, V> {
+    #[inline]
+    fn from(map: BTreeMap<K, V>) -> Self {
+        Map(map)
+    }
+}
+
+impl<K: Ord, V, S: BuildHasher> From<HashMap<K, V, S>> for Map<K, V> {
+    #[inline]
+    fn from(map: HashMap<K, V, S>) -> Self {
+        map.into_iter().collect()
+    }
+}
+
+impl<K: Ord, V> FromIterator<(K, V)> for Map<K, V> {
+    #[inline]
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        Map(iter.into_iter().collect())
+    }
+}
+
+impl<K: Ord, V> Extend<(K, V)> for Map<K, V> {
+    #[inline]
+    fn extend<I: IntoIterator<Item = (K, V)>>(&mut self, iter: I) {
+        self.0.extend(iter)
+    }
+}
+
+impl<K, V> IntoIterator for Map<K, V> {
+    type Item = (K, V);
+    type IntoIter = std::collections::btree_map::IntoIter<K, V>;
+    #[inline]
+    fn into_iter(self) -> Self::IntoIter {
+        self.0.into_iter()
+    }
+}
@@ -0,0 +1,89 @@
+use std::{
+    fs::File,
+    io::{BufRead, BufReader, Read, Write, Result},
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use memmap2::Mmap;
+
+use crate::Cache;
+
+/// A type that can be parsed directly from the bytes of a memory-mapped file, see [`Mapped`]
+pub trait MmapCache: Cache {
+    /// Parses the value from the bytes, which is all the stored data
+    fn from_mmap(map: &[u8]) -> Result<Self>;
+}
+
+/// Adapter that loads a [`MmapCache`] implementor by memory-mapping the cache file
+/// instead of reading it, while saving it normally
+///
+/// This avoids copying large files that are mostly read. Data not stored in a file is read into memory instead.
+///
+/// The file mustn't be modified in place while it's mapped, so building an instance that appends
+/// or doesn't use atomic writes fails with [`Unsupported`](std::io::ErrorKind::Unsupported), as does loading
+/// with a lazily built one.
+///
+/// # Safety
+/// Nothing prevents other programs from changing or truncating the file in place while it's mapped,
+/// which is undefined behaviour rather than an error, such as the process being killed by `SIGBUS`.
+/// Only use this for files that everything else also only replaces, e.g. by renaming a new file over them.
+///
+/// The cache file itself is mapped, so this doesn't work with a separate
+/// [`load_path`](crate::CachedBuilder::load_path) or [`search_paths`](crate::CachedBuilder::search_paths).
+/// Since the file isn't read through the reader, the first save after a load can't tell
+/// whether the value has changed, and always writes.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Mapped<T>(pub T);
+
+impl<T: MmapCache> Cache for Mapped<T> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        self.0.save(writer)
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(mut reader: R) -> Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        T::from_mmap(&data).map(Mapped)
+    }
+    fn load_from<R: BufRead>(_reader: R, path: &Path) -> Result<Self> {
+        let file = File::open(path)?;
+        // SAFETY: saves replace the file, as checked by `Cached`, and others mustn't modify it in place,
+        // as documented above
+        let map = unsafe { Mmap::map(&file)? };
+        T::from_mmap(&map).map(Mapped)
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.0.needs_rewrite()
+    }
+    #[inline]
+    fn needs_replacing_saves() -> bool {
+        true
+    }
+    fn merge(&mut self, disk: Self) {
+        self.0.merge(disk.0)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.0.diff(&old.0)
+    }
+    fn estimated_size(&self) -> usize {
+        self.0.estimated_size()
+    }
+}
+
+impl<T> Deref for Mapped<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Mapped<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,49 @@
+//! Ready-made [`Cache`](crate::Cache) implementations for common formats
+//!
+//! Formats needing other crates are behind their own feature,
+//! so that the core crate doesn't pull in any dependencies.
+
+#[cfg(feature = "bincode")]
+mod bincode;
+mod checksum;
+mod converted;
+mod custom;
+#[cfg(feature = "encryption")]
+mod encrypted;
+#[cfg(feature = "gzip")]
+mod gzip;
+#[cfg(feature = "serde")]
+mod json;
+#[cfg(feature = "serde")]
+mod jsonl;
+#[cfg(feature = "serde")]
+mod map;
+#[cfg(feature = "mmap")]
+mod mmap;
+#[cfg(feature = "serde")]
+mod multi;
+mod raw;
+mod tagged;
+mod versioned;
+
+#[cfg(feature = "bincode")]
+pub use self::bincode::Bincode;
+pub use self::checksum::{Checksummed, CHECKSUM_HEADER_LEN};
+pub use self::converted::Converted;
+pub use self::custom::Custom;
+#[cfg(feature = "encryption")]
+pub use self::encrypted::{Encrypted, EncryptionKey};
+#[cfg(feature = "gzip")]
+pub use self::gzip::{AutoDecompress, Gzip};
+#[cfg(feature = "serde")]
+pub use self::json::{Json, PrettyJson};
+#[cfg(feature = "serde")]
+pub use self::jsonl::Jsonl;
+#[cfg(feature = "serde")]
+pub use self::map::Map;
+#[cfg(feature = "mmap")]
+pub use self::mmap::{Mapped, MmapCache};
+#[cfg(feature = "serde")]
+pub use self::multi::MultiFormat;
+pub use self::tagged::{Tagged, TaggedCache};
+pub use self::versioned::{Migrate, Versioned, VERSION_HEADER_LEN, VERSION_MAGIC};
@@ -0,0 +1,106 @@
+use std::{
+    io::{BufRead, BufReader, Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+    path::Path,
+};
+
+use serde::{Serialize, de::DeserializeOwned};
+
+use crate::Cache;
+
+/// Wrapper that caches any serde type in the format given by the extension of the file
+///
+/// `.json` files use JSON, `.yaml` and `.yml` files use YAML with the `yaml` feature
+/// and `.toml` files use TOML with the `toml` feature. Other extensions fail with
+/// [`ErrorKind::Unsupported`]. Data that isn't stored in a file uses JSON.
+/// The extension of a compressed file is ignored, so `data.json.gz` uses JSON.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct MultiFormat<T>(pub T);
+
+/// A format chosen by the extension of the file
+#[derive(Debug, Clone, Copy)]
+pub(super) enum Format {
+    Json,
+    #[cfg(feature = "yaml")]
+    Yaml,
+    #[cfg(feature = "toml")]
+    Toml,
+}
+
+impl Format {
+    pub(super) fn from_path(path: &Path) -> Result<Self> {
+        match crate::storage::format_path(path).extension().and_then(|ext| ext.to_str()) {
+            Some("json") => Ok(Format::Json),
+            #[cfg(feature = "yaml")]
+            Some("yaml" | "yml") => Ok(Format::Yaml),
+            #[cfg(feature = "toml")]
+            Some("toml") => Ok(Format::Toml),
+            _ => Err(Error::new(
+                ErrorKind::Unsupported,
+                format!("no enabled format for the extension of {}", path.display()),
+            )),
+        }
+    }
+    /// Serializes the value in the format
+    pub(super) fn save<T: Serialize, W: Write>(self, value: &T, writer: W) -> Result<()> {
+        match self {
+            Format::Json => serde_json::to_writer(writer, value)?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::to_writer(writer, value).map_err(Error::other)?,
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                let mut writer = writer;
+                writer.write_all(toml::to_string(value).map_err(Error::other)?.as_bytes())?
+            }
+        }
+        Ok(())
+    }
+    /// Deserializes a value in the format
+    pub(super) fn load<T: DeserializeOwned, R: BufRead>(self, reader: R) -> Result<T> {
+        let value = match self {
+            Format::Json => serde_json::from_reader(reader)?,
+            #[cfg(feature = "yaml")]
+            Format::Yaml => serde_yaml::from_reader(reader).map_err(|e| Error::new(ErrorKind::InvalidData, e))?,
+            #[cfg(feature = "toml")]
+            Format::Toml => {
+                let (mut reader, mut s) = (reader, String::new());
+                reader.read_to_string(&mut s)?;
+                toml::from_str(&s).map_err(|e| Error::new(ErrorKind::InvalidData, e))?
+            }
+        };
+        Ok(value)
+    }
+}
+
+impl<T: Serialize + DeserializeOwned> Cache for MultiFormat<T> {
+    fn save<W: Write>(&self, writer: W) -> Result<()> {
+        Format::Json.save(&self.0, writer)
+    }
+    fn save_to<W: Write>(&self, writer: W, path: &Path) -> Result<()> {
+        Format::from_path(path)?.save(&self.0, writer)
+    }
+    fn load<R: Read>(reader: R) -> Result<Self> {
+        Self::load_buf(BufReader::new(reader))
+    }
+    fn load_buf<R: BufRead>(reader: R) -> Result<Self> {
+        Format::Json.load(reader).map(MultiFormat)
+    }
+    fn load_from<R: BufRead>(reader: R, path: &Path) -> Result<Self> {
+        Format::from_path(path)?.load(reader).map(MultiFormat)
+    }
+}
+
+impl<T> Deref for MultiFormat<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for MultiFormat<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
@@ -0,0 +1,27 @@
+use std::io::{Read, Write, Result};
+
+use crate::Cache;
+
+/// Caches the bytes of the file as they are
+impl Cache for Vec<u8> {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self)
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes)?;
+        Ok(bytes)
+    }
+}
+
+/// Caches the text of the file, which has to be valid UTF-8
+impl Cache for String {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(self.as_bytes())
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        Ok(s)
+    }
+}
@@ -0,0 +1,87 @@
+use std::{
+    io::{Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use crate::Cache;
+
+/// A type with variants, such as an enum, that are each saved and loaded in their own way
+/// behind a one-byte tag, see [`Tagged`]
+pub trait TaggedCache: Sized {
+    /// The tag of the value's variant
+    fn tag(&self) -> u8;
+    /// Write the data of the value's variant, which comes after its tag
+    fn save_variant<W: Write>(&self, writer: W) -> Result<()>;
+    /// Load the data of the variant with the given tag,
+    /// returning `None` if there is no variant with the tag
+    fn load_variant<R: Read>(tag: u8, reader: R) -> Result<Option<Self>>;
+    /// Like [`Cache::needs_rewrite`], which defaults to `false`
+    fn needs_rewrite(&self) -> bool {
+        false
+    }
+    /// Like [`Cache::merge`], which defaults to replacing the value with the loaded one
+    fn merge(&mut self, disk: Self) {
+        *self = disk;
+    }
+    /// Like [`Cache::diff`], which defaults to none
+    fn diff(&self, old: &Self) -> Vec<String> {
+        let _ = old;
+        Vec::new()
+    }
+    /// Like [`Cache::estimated_size`], which defaults to 0
+    fn estimated_size(&self) -> usize {
+        0
+    }
+}
+
+/// Adapter that caches a [`TaggedCache`] implementor as the tag of its variant followed by the variant's data
+///
+/// Loading fails with [`ErrorKind::InvalidData`] if the tag is missing or unknown.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub struct Tagged<T>(pub T);
+
+impl<T: TaggedCache> Cache for Tagged<T> {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&[self.0.tag()])?;
+        self.0.save_variant(writer)
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut tag = [0];
+        reader.read_exact(&mut tag).map_err(|e| if e.kind() == ErrorKind::UnexpectedEof {
+            Error::new(ErrorKind::InvalidData, "variant tag is missing")
+        } else {
+            e
+        })?;
+        match T::load_variant(tag[0], reader)? {
+            Some(value) => Ok(Tagged(value)),
+            None => Err(Error::new(ErrorKind::InvalidData, format!("unknown variant tag {:#04x}", tag[0]))),
+        }
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.0.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.0.merge(disk.0)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.0.diff(&old.0)
+    }
+    fn estimated_size(&self) -> usize {
+        self.0.estimated_size()
+    }
+}
+
+impl<T> Deref for Tagged<T> {
+    type Target = T;
+    #[inline]
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+impl<T> DerefMut for Tagged<T> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut T {
+        &mut self.0
+    }
+}
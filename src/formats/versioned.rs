@@ -0,0 +1,121 @@
+use std::{
+    io::{Error, ErrorKind, Read, Write, Result},
+    ops::{Deref, DerefMut},
+};
+
+use crate::Cache;
+
+/// Size in bytes of the header written by [`Versioned`]
+pub const VERSION_HEADER_LEN: usize = 8;
+
+/// Magic bytes starting the header written by [`Versioned`]
+pub const VERSION_MAGIC: [u8; 4] = *b"CSVR";
+
+/// A [`Cache`] implementor whose format has a version,
+/// so that data saved by older versions can be migrated when loading
+pub trait Migrate: Cache {
+    /// The version of the format written by [`Cache::save`]
+    ///
+    /// This should be increased whenever the format changes.
+    const VERSION: u32;
+    /// Loads data saved with the given older version of the format
+    fn migrate<R: Read>(version: u32, reader: R) -> Result<Self>;
+}
+
+/// Adapter that prefixes the data of a [`Migrate`] implementor with its format version,
+/// migrating data saved with older versions when loading
+///
+/// The header is [`VERSION_HEADER_LEN`] bytes: the [`VERSION_MAGIC`] bytes,
+/// followed by the version as a little-endian `u32`.
+/// Data with an older version is loaded with [`Migrate::migrate`]
+/// and then rewritten in the current format right away by [`Cached`](crate::Cached).
+/// Loading fails with [`ErrorKind::InvalidData`] if the header is missing or the version is newer.
+#[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
+pub struct Versioned<C> {
+    inner: C,
+    migrated_from: Option<u32>,
+}
+
+impl<C> Versioned<C> {
+    #[inline]
+    /// Wraps the value
+    pub fn new(inner: C) -> Self {
+        Versioned {
+            inner,
+            migrated_from: None,
+        }
+    }
+    #[inline]
+    /// Unwraps the value
+    pub fn into_inner(self) -> C {
+        self.inner
+    }
+    #[inline]
+    /// The version the value was migrated from when it was loaded, if it was
+    pub fn migrated_from(&self) -> Option<u32> {
+        self.migrated_from
+    }
+}
+
+impl<C: Migrate> Cache for Versioned<C> {
+    fn save<W: Write>(&self, mut writer: W) -> Result<()> {
+        writer.write_all(&VERSION_MAGIC)?;
+        writer.write_all(&C::VERSION.to_le_bytes())?;
+        self.inner.save(writer)
+    }
+    fn load<R: Read>(mut reader: R) -> Result<Self> {
+        let mut header = [0; VERSION_HEADER_LEN];
+        reader.read_exact(&mut header).map_err(|e| if e.kind() == ErrorKind::UnexpectedEof {
+            Error::new(ErrorKind::InvalidData, "version header is truncated")
+        } else {
+            e
+        })?;
+        if header[..4] != VERSION_MAGIC {
+            return Err(Error::new(ErrorKind::InvalidData, "version header is missing"));
+        }
+        let mut version = [0; 4];
+        version.copy_from_slice(&header[4..]);
+        let version = u32::from_le_bytes(version);
+
+        if version == C::VERSION {
+            C::load(reader).map(Versioned::new)
+        } else if version < C::VERSION {
+            Ok(Versioned {
+                inner: C::migrate(version, reader)?,
+                migrated_from: Some(version),
+            })
+        } else {
+            Err(Error::new(
+                ErrorKind::InvalidData,
+                format!("version {} is newer than the supported version {}", version, C::VERSION),
+            ))
+        }
+    }
+    fn needs_rewrite(&self) -> bool {
+        self.migrated_from.is_some() || self.inner.needs_rewrite()
+    }
+    fn merge(&mut self, disk: Self) {
+        self.inner.merge(disk.inner)
+    }
+    fn diff(&self, old: &Self) -> Vec<String> {
+        self.inner.diff(&old.inner)
+    }
+    fn estimated_size(&self) -> usize {
+        self.inner.estimated_size()
+    }
+}
+
+impl<C> Deref for Versioned<C> {
+    type Target = C;
+    #[inline]
+    fn deref(&self) -> &C {
+        &self.inner
+    }
+}
+
+impl<C> DerefMut for Versioned<C> {
+    #[inline]
+    fn deref_mut(&mut self) -> &mut C {
+        &mut self.inner
+    }
+}
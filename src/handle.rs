@@ -0,0 +1,68 @@
+use std::{
+    ops::Deref,
+    path::PathBuf,
+    rc::Rc,
+};
+
+use crate::{Cache, Cached, Result, storage::{FileStorage, Storage}};
+
+#[derive(Debug)]
+/// A cheaply clonable handle to a [`Cached`], so that it can be handed to several owners
+///
+/// All clones share the same instance, so they see the same inner value and share the tracking
+/// of when the cache file was last loaded or saved. The methods of [`Cached`] that only need a
+/// shared reference are available through `Deref`, such as [`with_mut`](Cached::with_mut) for mutations.
+///
+/// The sharing only works within a single thread, since the handle is reference counted with `Rc`.
+/// Other threads and processes using the same file are only noticed through the file itself,
+/// as with separate instances. Use [`SyncCached`](crate::sync::SyncCached) to share between threads.
+///
+/// A pending debounced save is done when the last handle is dropped.
+pub struct CachedHandle<T: Cache, S: Storage = FileStorage>(Rc<Cached<T, S>>);
+
+impl<T: Cache + Default> CachedHandle<T> {
+    #[inline]
+    /// Make a new instance using the type's default function, see [`Cached::new`]
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        Cached::new(path).map(CachedHandle::from)
+    }
+}
+
+impl<T: Cache, S: Storage> CachedHandle<T, S> {
+    #[inline]
+    /// Whether both handles share the same instance
+    pub fn ptr_eq(&self, other: &Self) -> bool {
+        Rc::ptr_eq(&self.0, &other.0)
+    }
+    #[inline]
+    /// How many handles share the instance
+    pub fn handle_count(&self) -> usize {
+        Rc::strong_count(&self.0)
+    }
+    /// Gives back the instance if this is the only handle to it
+    pub fn try_unwrap(self) -> Result<Cached<T, S>, Self> {
+        Rc::try_unwrap(self.0).map_err(CachedHandle)
+    }
+}
+
+impl<T: Cache, S: Storage> Clone for CachedHandle<T, S> {
+    #[inline]
+    fn clone(&self) -> Self {
+        CachedHandle(self.0.clone())
+    }
+}
+
+impl<T: Cache, S: Storage> From<Cached<T, S>> for CachedHandle<T, S> {
+    #[inline]
+    fn from(cached: Cached<T, S>) -> Self {
+        CachedHandle(Rc::new(cached))
+    }
+}
+
+impl<T: Cache, S: Storage> Deref for CachedHandle<T, S> {
+    type Target = Cached<T, S>;
+    #[inline]
+    fn deref(&self) -> &Cached<T, S> {
+        &self.0
+    }
+}
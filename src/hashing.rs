@@ -0,0 +1,136 @@
+use std::{
+    io::{Read, Write, Result},
+    collections::hash_map::DefaultHasher,
+    hash::Hasher,
+};
+
+/// Writer adapter that hashes and counts every byte passed through it
+pub(crate) struct HashWriter<W> {
+    inner: W,
+    hasher: DefaultHasher,
+    len: u64,
+}
+
+impl<W: Write> HashWriter<W> {
+    pub fn new(inner: W) -> Self {
+        HashWriter {
+            inner,
+            hasher: DefaultHasher::new(),
+            len: 0,
+        }
+    }
+    pub fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+    /// How many bytes have been written
+    pub fn len(&self) -> u64 {
+        self.len
+    }
+    pub fn into_inner(self) -> W {
+        self.inner
+    }
+}
+
+impl<W: Write> Write for HashWriter<W> {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.inner.write(buf)?;
+        self.hasher.write(&buf[..n]);
+        self.len += n as u64;
+        Ok(n)
+    }
+    fn flush(&mut self) -> Result<()> {
+        self.inner.flush()
+    }
+}
+
+#[cfg(feature = "tokio")]
+/// The hash of the bytes, which is what a [`HashWriter`] they were written to gives
+pub(crate) fn hash(bytes: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    hasher.write(bytes);
+    hasher.finish()
+}
+
+/// Reader adapter that hashes every byte read through it
+pub(crate) struct HashReader<R> {
+    inner: R,
+    hasher: DefaultHasher,
+}
+
+impl<R: Read> HashReader<R> {
+    pub fn new(inner: R) -> Self {
+        HashReader {
+            inner,
+            hasher: DefaultHasher::new(),
+        }
+    }
+    pub fn finish(&self) -> u64 {
+        self.hasher.finish()
+    }
+}
+
+impl<R: Read> Read for HashReader<R> {
+    fn read(&mut self, buf: &mut [u8]) -> Result<usize> {
+        let n = self.inner.read(buf)?;
+        self.hasher.write(&buf[..n]);
+        Ok(n)
+    }
+}
+
+/// Writer that computes the CRC-32 (IEEE) of every byte written to it
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct Crc32(u32);
+
+impl Crc32 {
+    pub fn new() -> Self {
+        Crc32(!0)
+    }
+    /// Continues from the CRC-32 of some data, for computing that of the data with more appended
+    pub fn resume(crc: u32) -> Self {
+        Crc32(!crc)
+    }
+    pub fn finish(&self) -> u32 {
+        !self.0
+    }
+}
+
+impl Default for Crc32 {
+    #[inline]
+    fn default() -> Self {
+        Crc32::new()
+    }
+}
+
+impl Write for Crc32 {
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.0 = buf.iter().fold(self.0, |crc, &b| CRC32_TABLE[((crc ^ b as u32) & 0xff) as usize] ^ (crc >> 8));
+        Ok(buf.len())
+    }
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// The CRC-32 (IEEE) of the data
+pub(crate) fn crc32(data: &[u8]) -> u32 {
+    let mut crc = Crc32::new();
+    let _ = crc.write(data);
+    crc.finish()
+}
+
+const CRC32_TABLE: [u32; 256] = {
+    let mut table = [0; 256];
+    let mut i = 0;
+    while i < 256 {
+        let mut crc = i as u32;
+        let mut bit = 0;
+        while bit < 8 {
+            crc = if crc & 1 != 0 { (crc >> 1) ^ 0xedb8_8320 } else { crc >> 1 };
+            bit += 1;
+        }
+        table[i] = crc;
+        i += 1;
+    }
+    table
+};
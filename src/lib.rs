@@ -2,90 +2,1704 @@
 //! An implementation of types that are cached in a file
 
 use std::{
-    io::{self, Read, Write, Result},
-    fs::{self, File},
-    cell::{RefCell, Ref},
+    fs::File,
+    io::{self, BufRead, Read, Write},
+    result::Result as StdResult,
+    cell::{Cell, RefCell, Ref, RefMut},
     path::{Path, PathBuf},
-    time::SystemTime,
+    time::{Duration, Instant, SystemTime},
+    mem,
+    sync::{Arc, Mutex, atomic::Ordering, mpsc::{self, Receiver, Sender}},
+    thread,
 };
 
+#[macro_use]
+mod logging;
+mod builder;
+mod clock;
+mod diff;
+mod edit;
+mod error;
+mod handle;
+mod hashing;
+mod persist;
+mod project;
+mod read_only;
+mod stats;
+mod two_phase;
+pub mod formats;
+pub mod storage;
+pub mod sync;
+#[cfg(feature = "tokio")]
+pub mod tokio;
+
+pub use builder::{CachedBuilder, LoadErrorPolicy};
+pub use clock::{Clock, FakeClock, SystemClock};
+pub use diff::Diff;
+pub use edit::EditGuard;
+pub use error::{CacheError, MutError, Result};
+pub use handle::CachedHandle;
+pub use project::ProjectedCached;
+pub use read_only::ReadOnlyCached;
+pub use stats::{CacheStats, ContentionStats};
+pub use two_phase::{PreparedSave, TwoPhase, commit_all};
+#[cfg(feature = "derive")]
+pub use cached_struct_derive::Cache;
+
+use builder::{ChangedPredicate, Factory, Options, ReloadHook, RetentionHook, Validator};
+use formats::Custom;
+use logging::StorageName;
+use persist::Codec;
+
+/// Merges a value loaded from the stored data into the in-memory one
+type Merge<'a, T> = &'a mut dyn FnMut(&mut T, T);
+
+/// How long to wait before the first retry of a load, increasing linearly with each retry
+const LOAD_RETRY_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Most times to wait for the stored data to settle before loading it anyway, see [`CachedBuilder::settle_delay`]
+const MAX_SETTLE_WAITS: usize = 10;
+
+/// Whether a load error looks like it was caused by reading partially written data
+fn is_partial(e: &io::Error) -> bool {
+    matches!(e.kind(), io::ErrorKind::InvalidData | io::ErrorKind::UnexpectedEof)
+}
+/// Whether a save error is one that might go away by trying again
+fn is_transient(kind: io::ErrorKind) -> bool {
+    matches!(kind, io::ErrorKind::Interrupted | io::ErrorKind::WouldBlock | io::ErrorKind::TimedOut)
+}
+use storage::{FileStorage, Storage, StorageLock, StorageMetadata};
+
 #[derive(Debug)]
 /// The wrapper type that handles the caching
-pub struct Cached<T: Cache> {
+pub struct Cached<T: Cache, S: Storage = FileStorage> {
     last_modified: RefCell<SystemTime>,
-    path: Box<Path>,
-    inner: RefCell<T>,
+    /// Length of the file when last loaded or saved
+    last_len: RefCell<u64>,
+    /// Identity of the file when last loaded or saved, see `StorageMetadata::file_id`
+    last_file_id: Cell<Option<(u64, u64)>>,
+    /// Hash of the serialized data last loaded from or saved to the file
+    content_hash: Cell<Option<u64>>,
+    /// When a debounced save is due, if one is pending
+    save_deadline: Cell<Option<Instant>>,
+    /// When the file was last checked for changes, if checks are throttled
+    last_checked: Cell<Option<Instant>>,
+    /// When the inner value was last saved
+    last_written: Cell<Option<Instant>>,
+    /// When the inner value was last loaded or saved
+    last_synced: Cell<Option<Instant>>,
+    stats: Cell<CacheStats>,
+    /// Whether a lazy instance hasn't checked the cache file yet,
+    /// so that the default inner value isn't saved over it
+    untouched: Cell<bool>,
+    /// Whether mutations skip saving, while in `without_autosave`
+    autosave_paused: Cell<bool>,
+    /// Clones the inner value for `last_saved`, if saves append
+    snapshot: Option<fn(&T) -> T>,
+    /// The inner value as it was last loaded or saved, if saves append
+    last_saved: RefCell<Option<T>>,
+    changed: Option<ChangedPredicate<T>>,
+    /// The inner value as it was last loaded or saved, if there is a change predicate
+    baseline: RefCell<Option<T>>,
+    on_reload: Option<ReloadHook<T>>,
+    retention: Option<RetentionHook<T>>,
+    validator: Option<Validator<T>>,
+    /// Channels of the receivers given by `subscribe`, which are sent to on every reload
+    subscribers: RefCell<Vec<Sender<SystemTime>>>,
+    /// Gives the value to reset to, if resetting is enabled or a default factory was set
+    factory: Option<Factory<T>>,
+    /// Saves and loads the inner value instead of its `Cache` implementation, if set
+    codec: Option<Codec<T>>,
+    options: Options,
+    storage: S,
+    /// Only `None` once moved out by `into_inner`, so that `drop` doesn't save it
+    inner: Option<RefCell<T>>,
 }
 
 impl<T: Cache + Default> Cached<T> {
     #[inline]
     /// Make a new instance using the type's default function
     pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
-        Self::new_with(T::default, path)
+        Self::builder(path).build()
+    }
+    #[inline]
+    /// Make a builder for configuring a new instance, using the type's default function
+    pub fn builder<P: Into<PathBuf>>(path: P) -> CachedBuilder<T> {
+        CachedBuilder::new(T::default, FileStorage::new(path))
+    }
+    #[inline]
+    /// Make a new instance using the type's default function without touching the cache file,
+    /// which is then loaded on first access
+    pub fn lazy<P: Into<PathBuf>>(path: P) -> Self {
+        Self::builder(path).build_lazy()
+    }
+    #[inline]
+    /// Make a new instance using the type's default function, wrapped for sharing between threads
+    ///
+    /// The whole instance is behind the mutex, so the lock is held across reloading,
+    /// mutating and saving, keeping track of the cache file consistently between threads.
+    /// [`SyncCached`](sync::SyncCached) instead lets readers run in parallel.
+    pub fn shared<P: Into<PathBuf>>(path: P) -> Result<Arc<Mutex<Self>>>
+    where T: Send {
+        Self::new(path).map(|cached| Arc::new(Mutex::new(cached)))
+    }
+    #[inline]
+    /// Make a new instance whose saves are debounced, see [`CachedBuilder::debounce`]
+    pub fn with_debounce<P: Into<PathBuf>>(path: P, debounce: Duration) -> Result<Self> {
+        Self::builder(path).debounce(debounce).build()
+    }
+}
+
+impl<T: Default + 'static> Cached<Custom<T>> {
+    /// Make a new instance that saves and loads the value with the given closures,
+    /// so that the type doesn't need to implement [`Cache`]
+    ///
+    /// The value is kept in a [`Custom`] wrapper, which derefs to it.
+    pub fn with_codec<P, SF, LF>(path: P, save: SF, load: LF) -> Result<Self>
+    where
+        P: Into<PathBuf>,
+        SF: Fn(&T, &mut dyn Write) -> io::Result<()> + Send + 'static,
+        LF: Fn(&mut dyn Read) -> io::Result<T> + Send + 'static,
+    {
+        let mut ret = Self::lazy(path);
+        ret.codec = Some(Codec {
+            save: Box::new(move |value, writer| save(&value.0, writer)),
+            load: Box::new(move |reader| load(reader).map(Custom)),
+        });
+        ret.check_load().map(|()| ret)
+    }
+    /// Make a new instance that saves and loads the value with its [`ContextCache`] implementation,
+    /// passing it the given context
+    ///
+    /// Like with [`with_codec`](Cached::with_codec), the value is kept in a [`Custom`] wrapper.
+    pub fn with_context<P: Into<PathBuf>>(path: P, context: T::Context) -> Result<Self>
+    where T: ContextCache {
+        let context = Arc::new(context);
+        let load_context = Arc::clone(&context);
+        Self::with_codec(
+            path,
+            move |value: &T, writer| value.save_with(writer, &context),
+            move |reader| T::load_with(reader, &load_context),
+        )
+    }
+}
+
+impl<T: Cache + Default, S: Storage> Cached<T, S> {
+    #[inline]
+    /// Make a new instance backed by the given storage, using the type's default function
+    pub fn with_storage(storage: S) -> Result<Self> {
+        Self::builder_with_storage(storage).build()
+    }
+    #[inline]
+    /// Make a builder for configuring a new instance backed by the given storage,
+    /// using the type's default function
+    pub fn builder_with_storage(storage: S) -> CachedBuilder<T, fn() -> T, S> {
+        CachedBuilder::new(T::default, storage)
+    }
+    /// Resets the inner value to the default and deletes the cache file,
+    /// so that the next load starts from scratch
+    ///
+    /// The default is given by the [`default_factory`](CachedBuilder::default_factory) if one was set.
+    /// Any pending debounced save is discarded.
+    pub fn clear(&self) -> Result<()> {
+        let _lock = self.lock()?;
+        self.storage.delete().map_err(CacheError::Save)?;
+
+        self.reset(self.factory.as_ref().map_or_else(T::default, Factory::make))
+    }
+    /// Resets the inner value to the default and saves it right away, keeping the cache file,
+    /// see [`reset_to`](Cached::reset_to)
+    ///
+    /// The default is given by the [`default_factory`](CachedBuilder::default_factory) if one was set.
+    pub fn reset_default(&self) -> Result<()> {
+        self.reset_to(self.factory.as_ref().map_or_else(T::default, Factory::make))
     }
 }
 
 impl<T: Cache> Cached<T> {
+    #[inline]
     /// Make a new instance using a custom default function
     pub fn new_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> Result<Self> {
-        let ret = Self {
-            last_modified: RefCell::new(SystemTime::UNIX_EPOCH),
-            path: path.into().into_boxed_path(),
-            inner: RefCell::new(default()),
-        };
-        ret.check_load().map(|()| ret)
+        Self::builder_with(default, path).build()
     }
-    fn check_load(&self) -> Result<()> {
-        let file_last_modified = match fs::metadata(&self.path) {
-            Ok(m) => m.modified()?,
-            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
-            Err(e) => return Err(e),
-        };
+    #[inline]
+    /// Make a new instance using the given value when the cache file doesn't exist
+    pub fn new_with_value<P: Into<PathBuf>>(value: T, path: P) -> Result<Self> {
+        Self::new_with(move || value, path)
+    }
+    #[inline]
+    /// Make a new instance using a custom default function without touching the cache file,
+    /// which is then loaded on first access
+    pub fn lazy_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> Self {
+        Self::builder_with(default, path).build_lazy()
+    }
+    /// Make a new instance whose inner value is loaded from the given bytes instead of the cache file,
+    /// without touching the cache file
+    ///
+    /// Like with [`lazy_with`](Cached::lazy_with), the cache file is checked on first access,
+    /// so if it exists then, it is loaded over the value from the bytes.
+    pub fn from_bytes<P: Into<PathBuf>>(path: P, bytes: &[u8]) -> Result<Self> {
+        let value = T::load(bytes).map_err(CacheError::Load)?;
+        Ok(Self::lazy_with(|| value, path))
+    }
+    /// Make a new instance using the value loaded from the given bytes when the cache file doesn't exist,
+    /// e.g. starter content in the format of the cache file embedded with `include_bytes!`
+    ///
+    /// The bytes are loaded with [`Cache::load`] even if the cache file exists,
+    /// so that broken embedded content is noticed right away.
+    pub fn new_with_embedded<P: Into<PathBuf>>(path: P, bytes: &'static [u8]) -> Result<Self> {
+        let value = T::load(bytes).map_err(CacheError::Load)?;
+        Self::new_with_value(value, path)
+    }
+    /// Make a new instance whose inner value is loaded from the given reader, e.g. from stdin or a socket,
+    /// and saved to the cache file at the given path right away, replacing it if it exists
+    ///
+    /// The path is only where the value is saved to from then on; the cache file isn't loaded first.
+    pub fn load_from_reader<P: Into<PathBuf>, R: Read>(path: P, reader: R) -> Result<Self> {
+        let value = T::load(reader).map_err(CacheError::Load)?;
+        let cached = Self::lazy_with(|| value, path);
+        cached.save()?;
+        cached.untouched.set(false);
+        Ok(cached)
+    }
+    /// Checks that the file at the given path loads successfully, without making an instance
+    ///
+    /// This is for checking a file before using it, e.g. before importing it with [`write_raw`](Cached::write_raw).
+    /// The file is loaded as a cache file at that path would be, but the loaded value is discarded.
+    pub fn validate_file<P: AsRef<Path>>(path: P) -> Result<()> {
+        let path = path.as_ref();
+        let file = File::open(path).map_err(error::with_path(path)).map_err(CacheError::Open)?;
+        persist::read_from::<T, _>(file, Some(path), None, None).map(drop)
+    }
+    #[inline]
+    /// Make a builder for configuring a new instance, using a custom default function
+    pub fn builder_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> CachedBuilder<T, F> {
+        CachedBuilder::new(default, FileStorage::new(path))
+    }
+    /// Make a new instance that loads whichever of the given files was modified most recently,
+    /// and saves to the first one, see [`CachedBuilder::search_paths`]
+    ///
+    /// # Panics
+    /// Panics if no paths are given.
+    pub fn new_multi<F: FnOnce() -> T>(paths: Vec<PathBuf>, default: F) -> Result<Self> {
+        let mut paths = paths.into_iter();
+        let path = paths.next().expect("no paths given");
+        Self::builder_with(default, path).search_paths(paths).build()
+    }
+    #[cfg(feature = "directories")]
+    /// Make a new instance with the cache file `<cache dir>/<app_name>/<filename>`,
+    /// where the cache directory is the platform's standard one, e.g. `~/.cache` on Linux
+    ///
+    /// The directory `<cache dir>/<app_name>` is created if it doesn't exist.
+    /// Failing to find or create it gives a [`CacheError::Save`].
+    pub fn in_cache_dir<F: FnOnce() -> T, P: AsRef<Path>>(app_name: &str, filename: P, default: F) -> Result<Self> {
+        let dirs = directories::BaseDirs::new()
+            .ok_or_else(|| CacheError::Save(io::Error::new(io::ErrorKind::NotFound, "no cache directory found")))?;
+        let dir = dirs.cache_dir().join(app_name);
+        std::fs::create_dir_all(&dir).map_err(CacheError::Save)?;
+        Self::new_with(default, dir.join(filename))
+    }
+    #[inline]
+    /// The path of the cache file
+    pub fn path(&self) -> &Path {
+        self.storage.path()
+    }
+    /// Makes file operations take an advisory lock,
+    /// same as [`CachedBuilder::locking`]
+    pub fn with_locking(mut self, locking: bool) -> Self {
+        self.storage.locking = locking;
+        self
+    }
+    /// Opens the cache file read-only and gives it to the closure, for operations on the file itself
+    ///
+    /// The cache file might be replaced by the next save, so the file shouldn't be kept open.
+    /// Changes made to the file this way bypass the instance, and are only
+    /// noticed when it checks the file's metadata like with any other change.
+    pub fn with_file<R, F: FnOnce(&File) -> R>(&self, f: F) -> Result<R> {
+        let _lock = self.lock()?;
+        self.storage.open().map(|file| f(&file)).map_err(CacheError::Open)
+    }
+    /// Sets the modification time of the cache file to now without rewriting it,
+    /// e.g. to signal tools watching it that the data is fresh
+    ///
+    /// Changes to the file are loaded first, so that they aren't mistaken for the touched file.
+    /// This also restarts the [time-to-live](CachedBuilder::ttl).
+    /// If the cache file doesn't exist, the inner value is saved to create it.
+    pub fn touch(&self) -> Result<()> {
+        let _lock = self.lock()?;
+        self.write_if_due()?;
+        self.refresh()?;
+        match self.storage.touch().map_err(CacheError::Save)? {
+            Some(metadata) => {
+                *self.last_modified.borrow_mut() = metadata.modified;
+                *self.last_len.borrow_mut() = metadata.len;
+                self.last_file_id.set(metadata.file_id);
+                self.last_synced.set(Some(self.now()));
+                Ok(())
+            }
+            None => self.write(),
+        }
+    }
+    /// Moves the cache file to the given path and makes the instance use it from now on,
+    /// e.g. for "save as"
+    ///
+    /// Changes to the cache file are loaded first, and if it doesn't exist yet, only the path changes,
+    /// so that the next save creates the file there. The file is moved with `fs::rename`,
+    /// which fails if the new path is on another filesystem. Backups and the lock file stay where they are.
+    pub fn rename_to<P: Into<PathBuf>>(&mut self, new_path: P) -> Result<()> {
+        let new_path = new_path.into();
+        {
+            let _lock = self.lock()?;
+            self.write_if_due()?;
+            self.refresh()?;
+            self.storage.move_to(&new_path).map_err(error::with_path(&new_path)).map_err(CacheError::Save)?;
+        }
+        self.storage.set_path(new_path);
+        if let Some(metadata) = persist::stat(&self.storage)? {
+            *self.last_modified.borrow_mut() = metadata.modified;
+            *self.last_len.borrow_mut() = metadata.len;
+            self.last_file_id.set(metadata.file_id);
+        }
+        Ok(())
+    }
+    #[inline]
+    /// Whether the cache file differs from that of the other instance, see [`storage::files_differ`]
+    ///
+    /// Only the files are compared, so unsaved changes to either inner value aren't taken into account.
+    pub fn differs_from<U: Cache>(&self, other: &Cached<U>) -> Result<bool> {
+        storage::files_differ(self.path(), other.path()).map_err(CacheError::Open)
+    }
+    /// The modification times and paths of the backups of the cache file, from the newest,
+    /// see [`CachedBuilder::backups`]
+    ///
+    /// Backups are named `<path>.bak.<n>`, and the `n`th entry here (counting from 1)
+    /// is the one restored by [`restore_backup(n)`](Cached::restore_backup).
+    pub fn backups(&self) -> Result<Vec<(SystemTime, PathBuf)>> {
+        self.storage.backups().map_err(CacheError::Metadata)
+    }
+    /// Like [`with_mut`](Cached::with_mut), but also appends a record of the mutation
+    /// to the audit log `<path>.log` once the value has been saved
+    ///
+    /// Each record is a line with the time in seconds since the Unix epoch and the label,
+    /// with any special characters in the label escaped, e.g. `1700000000.123 added user`.
+    /// The log is only ever appended to, and is flushed after each record.
+    ///
+    /// If appending to the log fails, that is reported like a failed save, with [`CacheError::Save`].
+    pub fn do_mut_logged<R, F: FnOnce(&mut T) -> R>(&self, label: &str, f: F) -> StdResult<R, MutError<R>> {
+        let r = self.with_mut(f)?;
+        let time = SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default();
+        let line = format!("{}.{:03} {}", time.as_secs(), time.subsec_millis(), label.escape_debug());
+        let logged = self.lock().and_then(|_lock| self.storage.append_log(&line).map_err(CacheError::Save));
+        match logged {
+            Ok(()) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Replaces the cache file with its `n`th backup (counting from 1 for the newest) and loads it,
+    /// discarding any unsaved changes to the inner value
+    ///
+    /// The backup is checked to load successfully before anything is replaced.
+    /// See [`CachedBuilder::backups`].
+    pub fn restore_backup(&self, n: usize) -> Result<()> {
+        let _lock = self.lock()?;
+        let path = self.storage.backup_path(n);
+        let backup = File::open(&path).map_err(error::with_path(&path)).map_err(CacheError::Open)?;
+        let (value, hash) = persist::read_from(backup, Some(self.path()), self.codec.as_ref(), self.options.buffer_size)?;
+        self.storage.restore_backup(n).map_err(CacheError::Save)?;
 
-        let mut last_modified = self.last_modified.borrow_mut();
+        self.save_deadline.set(None);
+        self.set_loaded(value, hash, &persist::existing_stat(&self.storage)?)
+    }
+    /// Consumes the instance, returning the inner `T`, the path of the cache file and
+    /// the modification time of the cache file when it was last loaded or saved
+    ///
+    /// Like with [`into_inner`](Cached::into_inner), nothing is saved.
+    pub fn into_parts(self) -> (T, PathBuf, SystemTime) {
+        let path = self.path().to_owned();
+        let last_modified = self.last_modified();
+        (self.into_inner(), path, last_modified)
+    }
+}
 
-        if *last_modified < file_last_modified {
-            let file = File::open(&self.path)?;
+impl<T: Cache, S: Storage> Cached<T, S> {
+    #[inline]
+    /// The storage backing the cache
+    pub fn storage(&self) -> &S {
+        &self.storage
+    }
+    /// Gives a receiver that gets the modification time of the stored data every time
+    /// the inner value is reloaded from it, such as after another process changed it
+    ///
+    /// Like the [`on_reload`](CachedBuilder::on_reload) callback, this only happens when the instance
+    /// checks for changes on access, never in the background. The receiver can be sent to another thread
+    /// while the instance is used in this one.
+    pub fn subscribe(&self) -> Receiver<SystemTime> {
+        let (sender, receiver) = mpsc::channel();
+        self.subscribers.borrow_mut().push(sender);
+        receiver
+    }
+    #[inline]
+    /// Turns the instance into a handle that can be cloned to share it, see [`CachedHandle`]
+    pub fn into_handle(self) -> CachedHandle<T, S> {
+        self.into()
+    }
+    #[inline]
+    /// The modification time of the cache file when it was last loaded or saved
+    ///
+    /// This is `UNIX_EPOCH` if it hasn't been loaded or saved yet.
+    pub fn last_modified(&self) -> SystemTime {
+        *self.last_modified.borrow()
+    }
+    #[inline]
+    /// Whether the inner value is loaded from and saved to the cache file, which is the case
+    /// unless the instance fell back to keeping it in memory, see [`CachedBuilder::fallback_in_memory`]
+    pub fn is_persistent(&self) -> bool {
+        !self.options.in_memory
+    }
+    /// Whether the cache file exists, without loading it
+    ///
+    /// This is `false` if its metadata can't be read.
+    pub fn file_exists(&self) -> bool {
+        matches!(persist::stat(&self.storage), Ok(Some(_)))
+    }
+    /// The current length of the cache file in bytes, without loading it,
+    /// or `None` if it doesn't exist
+    pub fn file_len(&self) -> Result<Option<u64>> {
+        persist::stat(&self.storage).map(|m| m.map(|m| m.len))
+    }
+    fn name(&self) -> StorageName<'_> {
+        StorageName(self.storage.file_path())
+    }
+    fn lock(&self) -> Result<Option<StorageLock<'_>>> {
+        if self.options.in_memory {
+            return Ok(None);
+        }
+        self.storage.lock().map_err(CacheError::Lock)
+    }
+    /// Takes the lock for bringing the inner value up to date with the stored data, which is shared
+    /// so that readers don't block each other, unless a pending save is due
+    ///
+    /// Rewriting the stored data on load, e.g. after a migration, is also done under the shared lock,
+    /// which is fine since other readers could only replace it with the same data.
+    fn read_lock(&self) -> Result<Option<StorageLock<'_>>> {
+        if self.options.in_memory {
+            return Ok(None);
+        }
+        match self.save_deadline.get() {
+            Some(deadline) if deadline <= self.now() => self.lock(),
+            _ => self.storage.lock_shared().map_err(CacheError::Lock),
+        }
+    }
+    fn check_load(&self) -> Result<()> {
+        let _lock = self.read_lock()?;
+        self.write_if_due()?;
+        self.refresh()
+    }
+    /// Loads the cache file if it has changed, assuming the lock is held
+    ///
+    /// Nothing is loaded while a debounced save is pending or autosaving is paused,
+    /// so that the unsaved changes aren't lost.
+    fn refresh(&self) -> Result<()> {
+        if self.save_deadline.get().is_some() || self.autosave_paused.get() || self.options.in_memory {
+            return Ok(());
+        }
+        if let Some(interval) = self.options.check_interval {
+            let now = self.now();
+            match self.last_checked.get() {
+                Some(last_checked) if now.duration_since(last_checked) < interval => return Ok(()),
+                _ => self.last_checked.set(Some(now)),
+            }
+        }
 
-            *self.inner.borrow_mut() = T::load(&file)?;
-            *last_modified = file_last_modified;
+        let refreshed = self.load_if_changed(self.options.load_error_policy);
+        match refreshed {
+            Ok(()) => self.untouched.set(false),
+            // Make sure the next access checks again
+            Err(_) => self.last_checked.set(None),
+        }
+        refreshed
+    }
+    /// Loads the cache file if it has changed, handling load errors with the given policy
+    fn load_if_changed(&self, policy: LoadErrorPolicy) -> Result<()> {
+        match persist::stat(&self.storage)? {
+            Some(mut metadata) => {
+                let last_modified = *self.last_modified.borrow();
+                let changed = if self.options.hash_based_reload {
+                    self.content_hash.get() != Some(persist::stored_hash(&self.storage)?)
+                } else {
+                    persist::has_changed(&metadata, last_modified, *self.last_len.borrow(), self.last_file_id.get())
+                };
+                if changed {
+                    metadata = match self.settle(metadata)? {
+                        Some(metadata) => metadata,
+                        // Replaced, so look again next time
+                        None => return Ok(()),
+                    };
+                }
+                if self.expired() || changed {
+                    debug!(
+                        "reloading {}: modified {:?}, last loaded or saved {:?}",
+                        self.name(), metadata.modified, last_modified,
+                    );
+                    match &self.factory {
+                        Some(factory) if self.options.empty_as_default && metadata.len == 0 => {
+                            debug!("{} is empty, using the default", self.name());
+                            self.set_loaded(factory.make(), persist::empty_hash(), &metadata)?;
+                        }
+                        _ => self.load(&metadata, Some(&mut T::merge), policy)?,
+                    }
+                } else {
+                    trace!("{} is unchanged since {:?}", self.name(), last_modified);
+                    self.count(|stats| stats.skipped_reloads += 1);
+                    if self.options.hash_based_reload {
+                        // The metadata might have changed without the data changing
+                        *self.last_modified.borrow_mut() = metadata.modified;
+                        *self.last_len.borrow_mut() = metadata.len;
+                        self.last_file_id.set(metadata.file_id);
+                    }
+                }
+            }
+            None => {
+                trace!("{} doesn't exist", self.name());
+                // The data was there if the inner value was loaded from or saved to it
+                if self.options.reset_on_missing && self.content_hash.get().is_some() {
+                    if let Some(factory) = &self.factory {
+                        debug!("{} disappeared, resetting to the default", self.name());
+                        self.reset(factory.make())?;
+                        self.notify_reload();
+                    }
+                }
+            }
         }
 
         Ok(())
     }
+    /// Waits for the stored data with the given metadata to stop changing, see [`CachedBuilder::settle_delay`],
+    /// giving its metadata once it has, or `None` if it disappeared in the meantime
+    fn settle(&self, mut metadata: StorageMetadata) -> Result<Option<StorageMetadata>> {
+        let delay = match self.options.settle_delay {
+            Some(delay) => delay,
+            None => return Ok(Some(metadata)),
+        };
+        for _ in 0..MAX_SETTLE_WAITS {
+            thread::sleep(delay);
+            match persist::stat(&self.storage)? {
+                Some(settled) if settled == metadata => return Ok(Some(metadata)),
+                Some(changed) => {
+                    trace!("{} is still changing", self.name());
+                    metadata = changed;
+                }
+                None => return Ok(None),
+            }
+        }
+        debug!("{} didn't settle, loading it anyway", self.name());
+        Ok(Some(metadata))
+    }
+    /// Fails if the type needs the stored data to be replaced by saves, see [`Cache::needs_replacing_saves`],
+    /// but saves append or write in place
+    fn check_saves_replace(&self) -> Result<()> {
+        if T::needs_replacing_saves() && (self.snapshot.is_some() || self.storage.writes_in_place()) {
+            return Err(CacheError::Load(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "this type can only be loaded if saves replace the stored data, without appending or writing in place",
+            )));
+        }
+        Ok(())
+    }
+    /// Whether saves have been cancelled with the cancellation flag
+    fn cancelled(&self) -> bool {
+        self.options.cancel.as_ref().is_some_and(|cancel| cancel.load(Ordering::Relaxed))
+    }
+    /// The current time from the clock
+    fn now(&self) -> Instant {
+        self.options.clock.as_ref().map_or_else(Instant::now, |clock| clock.now())
+    }
+    /// The cell of the inner value, which is only moved out when the instance is consumed
+    fn cell(&self) -> &RefCell<T> {
+        self.inner.as_ref().expect("inner value has been moved out")
+    }
+    /// Borrows the inner value, failing with [`CacheError::Busy`] if it is mutably borrowed
+    fn borrow(&self) -> Result<Ref<'_, T>> {
+        self.cell().try_borrow().map_err(|_| CacheError::Busy)
+    }
+    /// Mutably borrows the inner value, failing with [`CacheError::Busy`] if it is borrowed
+    fn borrow_mut(&self) -> Result<RefMut<'_, T>> {
+        self.cell().try_borrow_mut().map_err(|_| CacheError::Busy)
+    }
+    /// Sets the inner value as not corresponding to any stored data
+    fn reset(&self, value: T) -> Result<()> {
+        *self.borrow_mut()? = value;
+        *self.last_saved.borrow_mut() = None;
+        self.set_baseline(None);
+        *self.last_modified.borrow_mut() = SystemTime::UNIX_EPOCH;
+        *self.last_len.borrow_mut() = 0;
+        self.last_file_id.set(None);
+        self.content_hash.set(None);
+        self.save_deadline.set(None);
+        self.last_synced.set(None);
+        Ok(())
+    }
+    fn notify_reload(&self) {
+        if let Some(ReloadHook(on_reload)) = &self.on_reload {
+            on_reload(&self.cell().borrow());
+        }
+        let modified = *self.last_modified.borrow();
+        // Receivers that were dropped are forgotten
+        self.subscribers.borrow_mut().retain(|subscriber| subscriber.send(modified).is_ok());
+    }
+    /// Trims the inner value with the retention callback, if there is one
+    fn trim(&self) -> Result<()> {
+        if let Some(RetentionHook(trim)) = &self.retention {
+            trim(&mut *self.borrow_mut()?);
+        }
+        Ok(())
+    }
+    /// Checks the inner value with the validator, if there is one
+    fn validate(&self, inner: &T) -> Result<()> {
+        match &self.validator {
+            Some(Validator(validate)) => validate(inner).map_err(CacheError::Invalid),
+            None => Ok(()),
+        }
+    }
+    /// Whether the time-to-live has passed since the inner value was last loaded or saved
+    fn expired(&self) -> bool {
+        match (self.options.ttl, self.last_synced.get()) {
+            (Some(ttl), Some(last_synced)) => self.now().saturating_duration_since(last_synced) > ttl,
+            _ => false,
+        }
+    }
+    /// Reads the cache file into the inner value and records the given metadata as what was loaded
+    ///
+    /// If the inner value has unsaved changes, the loaded value is merged into it with `merge` if that is given.
+    /// Loading is retried if it fails on data that seems to be partially written, see [`CachedBuilder::load_retries`],
+    /// and then handled with the given policy.
+    fn load(&self, metadata: &StorageMetadata, merge: Option<Merge<'_, T>>, policy: LoadErrorPolicy) -> Result<()> {
+        self.check_saves_replace()?;
+        let mut metadata = *metadata;
+        let mut attempt = 0;
+        let read = loop {
+            let read = match self.options.max_size {
+                Some(max) if metadata.len > max => Err(CacheError::TooLarge(max)),
+                max_size => persist::read(&self.storage, self.codec.as_ref(), max_size, self.options.buffer_size),
+            };
+            match read {
+                Err(CacheError::Load(ref e)) if attempt < self.options.load_retries && is_partial(e) => {
+                    attempt += 1;
+                    thread::sleep(LOAD_RETRY_BACKOFF * attempt as u32);
+                    // The data might have been replaced in the meantime
+                    metadata = persist::existing_stat(&self.storage)?;
+                }
+                r => break r,
+            }
+        };
+        self.count(|stats| match read {
+            Ok(_) => stats.loads += 1,
+            Err(_) => stats.load_errors += 1,
+        });
+        match (read, &self.factory, policy) {
+            (Ok((value, hash)), _, _) if merge.is_some() && self.content_hash.get().is_some() && self.is_dirty()? => {
+                debug!("merging unsaved changes with {}", self.name());
+                self.set_baseline(Some(&value));
+                if let Some(merge) = merge {
+                    merge(&mut *self.borrow_mut()?, value);
+                }
+                // The merged value has to be saved in full
+                *self.last_saved.borrow_mut() = None;
+                self.set_synced(hash, &metadata);
+                self.notify_reload();
+                Ok(())
+            }
+            (Ok((value, hash)), _, _) => self.set_loaded(value, hash, &metadata),
+            (Err(CacheError::Load(e)), Some(factory), policy) if policy != LoadErrorPolicy::Propagate => {
+                debug!("failed to load {}, using the default: {}", self.name(), e);
+                *self.borrow_mut()? = factory.make();
+                *self.last_saved.borrow_mut() = None;
+                *self.last_modified.borrow_mut() = metadata.modified;
+                *self.last_len.borrow_mut() = metadata.len;
+                self.last_file_id.set(metadata.file_id);
+                // Unknown, so that the next save isn't skipped
+                self.content_hash.set(None);
+                self.set_baseline(None);
+                self.last_synced.set(Some(self.now()));
+                if policy == LoadErrorPolicy::UseDefaultAndOverwrite && !self.options.read_only {
+                    self.write()?;
+                }
+                self.notify_reload();
+                Ok(())
+            }
+            (Err(e), _, _) => Err(e),
+        }
+    }
+    fn count<F: FnOnce(&mut CacheStats)>(&self, f: F) {
+        let mut stats = self.stats.get();
+        f(&mut stats);
+        self.stats.set(stats);
+    }
+    /// Sets the inner value to one loaded from data with the given hash and metadata
+    fn set_loaded(&self, value: T, hash: u64, metadata: &StorageMetadata) -> Result<()> {
+        let hash = match self.snapshot {
+            // The stored data is a log, so compare with how the value would be saved in full instead
+            Some(snapshot) => {
+                *self.last_saved.borrow_mut() = Some(snapshot(&value));
+                persist::hash(&self.storage, &value, self.codec.as_ref())?
+            }
+            None => hash,
+        };
+        let rewrite = !self.options.read_only && value.needs_rewrite();
+        self.set_baseline(Some(&value));
+        *self.borrow_mut()? = value;
+        self.set_synced(hash, metadata);
+        if rewrite {
+            // Appending to data in the old format wouldn't work
+            *self.last_saved.borrow_mut() = None;
+            self.write()?;
+        }
+        self.notify_reload();
+        Ok(())
+    }
+    /// Keeps a clone of the value the stored data holds for the change predicate, if there is one
+    fn set_baseline(&self, value: Option<&T>) {
+        if let Some(predicate) = &self.changed {
+            *self.baseline.borrow_mut() = value.map(predicate.clone);
+        }
+    }
+    /// Whether the inner value is the same as what was last loaded or saved, as decided by the change predicate
+    /// or otherwise by comparing the hash of its serialized form
+    fn unchanged(&self) -> Result<bool> {
+        match (&self.changed, &*self.baseline.borrow()) {
+            (Some(predicate), Some(baseline)) => Ok(!(predicate.changed)(baseline, &*self.borrow()?)),
+            _ => Ok(self.content_hash.get() == Some(persist::hash(&self.storage, &*self.borrow()?, self.codec.as_ref())?)),
+        }
+    }
+    /// Records that the stored data with the given hash and metadata matches the inner value
+    fn set_synced(&self, hash: u64, metadata: &StorageMetadata) {
+        *self.last_modified.borrow_mut() = metadata.modified;
+        *self.last_len.borrow_mut() = metadata.len;
+        self.last_file_id.set(metadata.file_id);
+        self.content_hash.set(Some(hash));
+        self.last_synced.set(Some(self.now()));
+    }
+    /// Re-reads the cache file even if it doesn't seem to have been modified,
+    /// discarding any unsaved changes to the inner value
+    ///
+    /// This is useful on filesystems with a coarse modification time granularity,
+    /// where changes made shortly after the last load can go undetected.
+    pub fn reload(&self) -> Result<()> {
+        let _lock = self.read_lock()?;
+        self.load(&persist::existing_stat(&self.storage)?, None, self.options.load_error_policy)
+    }
+    /// Forgets what is known about the cache file and checks it again like [`get`](Cached::get) does,
+    /// ignoring the check interval, so that whatever is now at the path is loaded
+    ///
+    /// This is for when the directory of the cache file has been replaced, e.g. when a volume
+    /// is remounted in a container, after which the new file could otherwise go undetected
+    /// if it happens to look like the old one or the check interval hasn't passed.
+    /// If the new directory doesn't have the cache file, it's handled like it was deleted.
+    /// Nothing is loaded while a debounced save is pending, which is saved to the new directory instead.
+    pub fn reopen(&self) -> Result<()> {
+        let _lock = self.read_lock()?;
+        debug!("reopening {}", self.name());
+        *self.last_modified.borrow_mut() = SystemTime::UNIX_EPOCH;
+        *self.last_len.borrow_mut() = 0;
+        self.last_file_id.set(None);
+        self.last_checked.set(None);
+        self.write_if_due()?;
+        self.refresh()
+    }
+    #[inline]
+    /// Loads the cache file even if it doesn't seem to have been modified, merging it into
+    /// any unsaved changes to the inner value with [`Cache::merge`], e.g. for pulling in
+    /// others' changes on a timer
+    ///
+    /// The loaded value is merged into the in-memory one, which is the `self` of `merge`,
+    /// and the merged value is only saved with the next save. Without unsaved changes,
+    /// the inner value is simply replaced like with [`reload`](Cached::reload).
+    /// Nothing happens if the cache file doesn't exist.
+    pub fn sync(&self) -> Result<()> {
+        self.sync_with(T::merge)
+    }
+    /// Like [`sync`](Cached::sync), but merging with the given closure instead of [`Cache::merge`]
+    ///
+    /// The closure is given the in-memory value to merge the loaded value into.
+    pub fn sync_with<F: FnMut(&mut T, T)>(&self, mut merge: F) -> Result<()> {
+        let _lock = self.read_lock()?;
+        match persist::stat(&self.storage)? {
+            Some(metadata) if !self.options.in_memory => {
+                self.load(&metadata, Some(&mut merge), self.options.load_error_policy)?;
+                self.untouched.set(false);
+                Ok(())
+            }
+            _ => Ok(()),
+        }
+    }
     fn save(&self) -> Result<()> {
-        let mut file = File::create(&self.path)?;
+        let _lock = self.lock()?;
+        self.write()
+    }
+    /// Writes the inner value to the cache file, assuming the lock is held
+    fn write(&self) -> Result<()> {
+        self.write_teed::<io::Sink>().map(drop)
+    }
+    /// Like [`write`](Cached::write), but also passing the bytes of a full write to a new `D`, which is returned
+    fn write_teed<D: Write + Default>(&self) -> Result<D> {
+        if self.options.in_memory {
+            trace!("not saving {}, since it's only kept in memory", self.name());
+            return Ok(D::default());
+        }
+        debug!("saving {}", self.name());
+        self.trim()?;
+        let inner = self.borrow()?;
+        self.validate(&inner)?;
+        let written = match &*self.last_saved.borrow() {
+            Some(since) => persist::append(&self.storage, &*inner, since, self.options.buffer_size)
+                .and_then(|metadata| Ok((metadata, persist::hash(&self.storage, &*inner, self.codec.as_ref())?, D::default()))),
+            None => {
+                let mut attempt = 0;
+                loop {
+                    let (cancel, buffer_size, preallocate) =
+                        (self.options.cancel.as_deref(), self.options.buffer_size, self.options.preallocate);
+                    match persist::write_teed(&self.storage, &*inner, self.codec.as_ref(), cancel, buffer_size, preallocate, D::default()) {
+                        Err(ref e) if attempt < self.options.save_retries && is_transient(e.kind()) && !self.cancelled() => {
+                            debug!("retrying save of {}: {}", self.name(), e);
+                            thread::sleep(self.options.save_backoff * 2u32.saturating_pow(attempt as u32));
+                            attempt += 1;
+                        }
+                        r => break r,
+                    }
+                }
+            }
+        };
+        self.count(|stats| match written {
+            Ok(_) => stats.saves += 1,
+            Err(_) => stats.save_errors += 1,
+        });
+        let (metadata, hash, tee) = written?;
+        if let Some(snapshot) = self.snapshot {
+            *self.last_saved.borrow_mut() = Some(snapshot(&inner));
+        }
 
-        self.inner.borrow().save(&mut file)?;
-        *self.last_modified.borrow_mut() = file.metadata()?.modified()?;
+        self.save_deadline.set(None);
+        self.last_written.set(Some(self.now()));
+        self.set_baseline(Some(&inner));
+        self.set_synced(hash, &metadata);
+        debug!("saved {}: modified {:?}, {} bytes", self.name(), metadata.modified, metadata.len);
+        Ok(tee)
+    }
+    /// Saves the inner value only if its serialized form differs from
+    /// what was last loaded from or saved to the cache file
+    ///
+    /// Returns whether the file was written.
+    pub fn save_if_dirty(&self) -> Result<bool> {
+        let _lock = self.lock()?;
+        self.write_if_dirty()
+    }
+    /// Whether the inner value has unsaved changes, that is, whether its serialized form differs from
+    /// what was last loaded from or saved to the cache file, or what the
+    /// [`changed_predicate`](CachedBuilder::changed_predicate) decides if one is set
+    ///
+    /// This is what [`save_if_dirty`](Cached::save_if_dirty) checks;
+    /// the cache file isn't read, so changes to it since then aren't taken into account.
+    pub fn is_dirty(&self) -> Result<bool> {
+        if self.untouched.get() {
+            Ok(false)
+        } else {
+            self.unchanged().map(|unchanged| !unchanged)
+        }
+    }
+    fn write_if_dirty(&self) -> Result<bool> {
+        if self.untouched.get() {
+            Ok(false)
+        } else if self.unchanged()? {
+            trace!("not saving {}, since the value is unchanged", self.name());
+            self.save_deadline.set(None);
+            Ok(false)
+        } else {
+            self.write().map(|()| true)
+        }
+    }
+    /// Saves after a mutation, or schedules a save when saves are debounced
+    fn write_after_mut(&self) -> Result<()> {
+        if self.autosave_paused.get() {
+            return Ok(());
+        }
+        match self.postponed_until() {
+            Some(deadline) => self.save_deadline.set(Some(deadline)),
+            None => {
+                self.write_if_dirty()?;
+            }
+        }
         Ok(())
     }
+    /// When to save after a mutation, if the save is postponed by debouncing or throttling
+    fn postponed_until(&self) -> Option<Instant> {
+        let now = self.now();
+        if let Some(debounce) = self.options.debounce {
+            return Some(now + debounce);
+        }
+        let next = self.last_written.get()? + self.options.max_save_rate?;
+        (next > now).then_some(next)
+    }
+    /// Does the pending debounced save if it's due
+    fn write_if_due(&self) -> Result<()> {
+        match self.save_deadline.get() {
+            Some(deadline) if deadline <= self.now() => self.write_if_dirty().map(|_| ()),
+            _ => Ok(()),
+        }
+    }
+    #[inline]
+    /// Whether a save is pending, because saves are debounced or throttled
+    /// (see [`CachedBuilder::max_save_rate`])
+    pub fn pending_save(&self) -> bool {
+        self.save_deadline.get().is_some()
+    }
+    /// Does the pending debounced save right away, if there is one
+    ///
+    /// Returns whether the file was written.
+    pub fn save_pending(&self) -> Result<bool> {
+        if self.save_deadline.get().is_some() {
+            self.save_if_dirty()
+        } else {
+            Ok(false)
+        }
+    }
+    /// Runs the closure with saving after mutations paused, and then saves once if anything changed
+    ///
+    /// Mutations like [`with_mut`](Cached::with_mut) made by the closure don't save, and the cache file
+    /// isn't reloaded in the meantime, so that the changes aren't lost. This batches the saves of code
+    /// that mutates through this instance, without having to change it. Calls can be nested,
+    /// in which case only the outermost one saves. Saving is resumed even if the closure panics.
+    ///
+    /// If the save fails, the closure's result is still available from the error, see [`MutError::Unsaved`].
+    pub fn without_autosave<R, F: FnOnce() -> R>(&self, f: F) -> StdResult<R, MutError<R>> {
+        if self.autosave_paused.get() {
+            return Ok(f());
+        }
+        /// Resumes saving when dropped
+        struct Resume<'a>(&'a Cell<bool>);
+        impl Drop for Resume<'_> {
+            fn drop(&mut self) {
+                self.0.set(false);
+            }
+        }
+        let r = {
+            self.autosave_paused.set(true);
+            let _resume = Resume(&self.autosave_paused);
+            f()
+        };
+        match self.save_if_dirty() {
+            Ok(_) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Writes the current in-memory value to the cache file,
+    /// regardless of whether it has been changed
+    ///
+    /// This can be used to retry a save that failed, such as after freeing disk space,
+    /// with [`is_dirty`](Cached::is_dirty) telling whether there's anything to retry.
+    pub fn save_now(&self) -> Result<()> {
+        self.save()
+    }
+    #[inline]
+    /// Same as [`save_now`](Cached::save_now), e.g. for retrying a save that failed
+    /// once [`is_dirty`](Cached::is_dirty) tells there are unsaved changes
+    pub fn force_save(&self) -> Result<()> {
+        self.save_now()
+    }
+    /// Writes the current in-memory value to the cache file like [`save_now`](Cached::save_now),
+    /// but only if the file's modification time is still `expected`, returning whether it was written
+    ///
+    /// This is for optimistic concurrency between processes: take [`last_modified`](Cached::last_modified),
+    /// change the value, and try saving it, starting over from a fresh load if someone else saved in between.
+    /// A missing file only matches [`UNIX_EPOCH`](SystemTime::UNIX_EPOCH), as is the modification time before
+    /// anything has been loaded or saved. When `expected` is that of what was last loaded or saved,
+    /// the length and file ID have to match too, since changes within the granularity of the modification time,
+    /// which is as coarse as a few seconds on some filesystems, keep it the same.
+    /// The check and the write only exclude other writers in between when [`locking`](CachedBuilder::locking) is enabled.
+    pub fn compare_and_save(&self, expected: SystemTime) -> Result<bool> {
+        let _lock = self.lock()?;
+        let unchanged = match persist::stat(&self.storage)? {
+            Some(metadata) => {
+                metadata.modified == expected
+                    && (expected != *self.last_modified.borrow()
+                        || !persist::has_changed(&metadata, expected, *self.last_len.borrow(), self.last_file_id.get()))
+            }
+            None => expected == SystemTime::UNIX_EPOCH,
+        };
+        if !unchanged {
+            debug!("not saving {}, since it was changed by someone else", self.name());
+            return Ok(false);
+        }
+        self.write().map(|()| true)
+    }
+    #[cfg(feature = "sha2")]
+    /// Like [`save_now`](Cached::save_now), but also returns the SHA-256 digest of the saved data,
+    /// e.g. for using it as a key for what is derived from it
+    ///
+    /// The bytes are hashed as they are written, so the digest covers exactly what was written
+    /// without reading the file again. When saves append, see [`CachedBuilder::append`],
+    /// the data is rewritten in full so that the digest covers all of it.
+    pub fn save_and_digest(&self) -> Result<[u8; 32]> {
+        use sha2::{Digest, Sha256};
+
+        let _lock = self.lock()?;
+        *self.last_saved.borrow_mut() = None;
+        Ok(self.write_teed::<Sha256>()?.finalize().into())
+    }
+    /// Reads the contents of the cache file as they are, without loading them
+    ///
+    /// A due debounced save is done first, but a pending one isn't.
+    pub fn read_raw(&self) -> Result<Vec<u8>> {
+        let _lock = self.read_lock()?;
+        self.write_if_due()?;
+        let mut reader = self.storage.read().map_err(CacheError::Open)?;
+        let mut bytes = Vec::new();
+        reader.read_to_end(&mut bytes).map_err(CacheError::Load)?;
+        Ok(bytes)
+    }
+    /// Replaces the contents of the cache file with the given bytes, and sets the inner value
+    /// to what they load as
+    ///
+    /// The bytes are checked to load successfully before anything is written,
+    /// and any unsaved changes to the inner value are discarded.
+    pub fn write_raw(&self, bytes: &[u8]) -> Result<()> {
+        let _lock = self.lock()?;
+        let (value, _) = persist::read_from(bytes, self.storage.file_path(), self.codec.as_ref(), None)?;
+        let written = persist::write_raw(&self.storage, bytes);
+        self.count(|stats| match written {
+            Ok(_) => stats.saves += 1,
+            Err(_) => stats.save_errors += 1,
+        });
+        let (metadata, hash) = written?;
+
+        self.save_deadline.set(None);
+        self.untouched.set(false);
+        self.set_loaded(value, hash, &metadata)
+    }
     /// Get a reference to the inner type
-    pub fn get(&self) -> Result<Ref<T>> {
+    pub fn get(&self) -> Result<Ref<'_, T>> {
         self.check_load()?;
-        Ok(self.inner.borrow())
+        self.borrow()
     }
-    /// Applies the given closure to a mutable reference to the inner value
-    /// and automatically saves the state to the cache file afterwards
-    /// 
-    /// **Note**: Currently, it might still return an error, even if the closure was run.
-    pub fn do_mut<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> Result<R> {
+    /// Like [`get`](Cached::get), but gives a reference to a part of the inner value,
+    /// such as a field, selected by the closure
+    pub fn field<R: ?Sized, F: FnOnce(&T) -> &R>(&self, f: F) -> Result<Ref<'_, R>> {
+        self.get().map(|inner| Ref::map(inner, f))
+    }
+    #[inline]
+    /// Makes a view of a part of the inner value, which is read with `get` and replaced with `set`,
+    /// while the whole inner value is what is saved, see [`ProjectedCached`]
+    pub fn project<U, G, P>(&self, get: G, set: P) -> ProjectedCached<'_, T, G, P, S>
+    where
+        G: Fn(&T) -> &U,
+        P: Fn(&mut T, U),
+    {
+        ProjectedCached::new(self, get, set)
+    }
+    /// Like [`get`](Cached::get), but gives `None` while the inner value is the default one
+    /// because nothing has been loaded from or saved to the cache file yet
+    ///
+    /// This tells apart a missing cache file from one holding a value equal to the default.
+    pub fn get_opt(&self) -> Result<Option<Ref<'_, T>>> {
+        self.check_load()?;
+        if self.content_hash.get().is_some() {
+            self.borrow().map(Some)
+        } else {
+            Ok(None)
+        }
+    }
+    /// Like [`get`](Cached::get), but gives `None` without loading anything
+    /// unless the cache file was modified after the given time
+    ///
+    /// This is for processing the cache file incrementally, with the time it was last processed
+    /// tracked elsewhere. The file is loaded as with `get`, so it isn't read again if it's unchanged
+    /// since the instance last loaded or saved it.
+    pub fn load_if_newer_than(&self, time: SystemTime) -> Result<Option<Ref<'_, T>>> {
+        match persist::stat(&self.storage)? {
+            Some(metadata) if metadata.modified > time => self.get().map(Some),
+            _ => Ok(None),
+        }
+    }
+    /// Like [`get`](Cached::get), but if nothing has been loaded from or saved to the cache file
+    /// yet (see [`get_opt`](Cached::get_opt)), the inner value is set to the result of `init`
+    /// and saved right away first
+    ///
+    /// Unlike a default function, `init` is only run when the cache file doesn't exist.
+    pub fn get_or_init<F: FnOnce() -> T>(&self, init: F) -> Result<Ref<'_, T>> {
+        {
+            let _lock = self.lock()?;
+            self.write_if_due()?;
+            self.refresh()?;
+            if self.content_hash.get().is_none() {
+                *self.borrow_mut()? = init();
+                self.write()?;
+            }
+        }
+        self.borrow()
+    }
+    /// Loads only the given keys from the cache file into a new value,
+    /// leaving the inner value alone, see [`PartialCache`]
+    ///
+    /// This always reads the cache file, and gives the default value if it doesn't exist.
+    /// Unsaved changes to the inner value aren't included.
+    pub fn get_partial(&self, keys: &[&str]) -> Result<T>
+    where T: PartialCache + Default {
+        let _lock = self.read_lock()?;
+        if persist::stat(&self.storage)?.is_none() {
+            return Ok(T::default());
+        }
+        let reader = persist::buffered_reader(self.storage.read().map_err(CacheError::Open)?, self.options.buffer_size);
+        T::load_keys(reader, keys).map_err(CacheError::Load)
+    }
+    /// Calls the closure with each record in the cache file, leaving the inner value alone,
+    /// see [`StreamingCache`]
+    ///
+    /// This always reads the cache file, and does nothing if it doesn't exist.
+    /// A due debounced save is done first, but unsaved changes to the inner value aren't included otherwise.
+    pub fn for_each_record<F: FnMut(T::Record)>(&self, f: F) -> Result<()>
+    where T: StreamingCache {
+        let _lock = self.read_lock()?;
+        self.write_if_due()?;
+        if persist::stat(&self.storage)?.is_none() {
+            return Ok(());
+        }
+        let reader = persist::buffered_reader(self.storage.read().map_err(CacheError::Open)?, self.options.buffer_size);
+        T::load_streaming(reader, f).map_err(CacheError::Load)
+    }
+    /// Brings the inner value up to date with the cache file like [`get`](Cached::get) does,
+    /// returning whether the cache file was loaded
+    pub fn reload_if_changed(&self) -> Result<bool> {
+        let loads = self.stats.get().loads;
+        self.check_load()?;
+        Ok(self.stats.get().loads != loads)
+    }
+    /// Loads the cache file if it has changed, returning whether it was loaded, like
+    /// [`reload_if_changed`](Cached::reload_if_changed), but keeping the inner value if the file can't be parsed
+    ///
+    /// The parse error is returned regardless of the [`LoadErrorPolicy`], and the cache file is
+    /// still considered changed, so it's loaded again once it's fixed. This is for carrying on
+    /// with the last good value when another process writes a malformed file.
+    /// The check interval is ignored, and nothing is loaded while a debounced save is pending.
+    pub fn try_reload(&self) -> Result<bool> {
+        let _lock = self.read_lock()?;
+        self.write_if_due()?;
+        if self.save_deadline.get().is_some() || self.autosave_paused.get() || self.options.in_memory {
+            return Ok(false);
+        }
+        let loads = self.stats.get().loads;
+        self.load_if_changed(LoadErrorPolicy::Propagate)?;
+        self.untouched.set(false);
+        Ok(self.stats.get().loads != loads)
+    }
+    /// Brings the inner value up to date with the cache file like [`get`](Cached::get) does,
+    /// describing what happened, e.g. for reacting to changes to the cache file from an event loop
+    pub fn poll(&self) -> Result<CacheEvent> {
+        if self.reload_if_changed()? {
+            Ok(CacheEvent::Reloaded(self.last_modified()))
+        } else if persist::stat(&self.storage)?.is_none() {
+            Ok(CacheEvent::FileMissing)
+        } else {
+            Ok(CacheEvent::Unchanged)
+        }
+    }
+    /// Like [`get`](Cached::get), but also tells whether the cache file was loaded to bring the value up to date
+    pub fn get_with_status(&self) -> Result<(Ref<'_, T>, bool)> {
+        let loaded = self.reload_if_changed()?;
+        Ok((self.borrow()?, loaded))
+    }
+    #[inline]
+    /// How many times the cache file has been loaded, including when the instance was made
+    ///
+    /// Together with counting accesses, this tells how often the in-memory value could be used as is.
+    pub fn reload_count(&self) -> u64 {
+        self.stats.get().loads
+    }
+    #[inline]
+    /// Counters of the loads and saves done so far
+    pub fn stats(&self) -> CacheStats {
+        self.stats.get()
+    }
+    #[inline]
+    /// Get a reference to the inner value as it is in memory,
+    /// without checking whether the cache file has changed
+    ///
+    /// This never does I/O, so it can't fail, but the value may be stale compared to the file.
+    /// Use [`get`](Cached::get) for an up-to-date value.
+    ///
+    /// # Panics
+    /// Panics if the inner value is currently mutably borrowed.
+    pub fn peek(&self) -> Ref<'_, T> {
+        self.cell().borrow()
+    }
+    /// Roughly how many bytes the inner value takes up in memory, as estimated by [`Cache::estimated_size`],
+    /// e.g. for deciding which of many caches to drop to stay within a memory budget
+    ///
+    /// This never does I/O and doesn't check whether the cache file has changed.
+    ///
+    /// # Panics
+    /// Panics if the inner value is currently mutably borrowed.
+    pub fn memory_estimate(&self) -> usize {
+        self.cell().borrow().estimated_size()
+    }
+    /// Serializes the inner value to the given writer in the same format as it's saved in,
+    /// without touching the cache file
+    ///
+    /// This is useful for printing the value for debugging, e.g. with `dump(io::stdout())`.
+    /// The cache file is loaded first if it has changed, like with [`get`](Cached::get).
+    pub fn dump<W: Write>(&self, mut writer: W) -> Result<()> {
+        self.check_load()?;
+        persist::dump(&self.storage, &*self.borrow()?, self.codec.as_ref(), &mut writer)
+    }
+    /// The size in bytes the inner value would be saved with, found by serializing it without writing anything
+    ///
+    /// This can be compared to [`CachedBuilder::max_size`] before saving. It is the full size,
+    /// even if saves only append. The cache file is loaded first if it has changed, like with [`get`](Cached::get).
+    pub fn serialized_len(&self) -> Result<u64> {
+        self.check_load()?;
+        persist::serialized_len(&self.storage, &*self.borrow()?, self.codec.as_ref())
+    }
+    /// Applies the given closure to a reference to the inner value and returns its result,
+    /// releasing the borrow right away
+    pub fn read<R, F: FnOnce(&T) -> R>(&self, f: F) -> Result<R> {
+        self.check_load()?;
+        Ok(f(&*self.borrow()?))
+    }
+    #[inline]
+    /// Gives a clone of the inner value, brought up to date with the cache file like [`get`](Cached::get) does
+    ///
+    /// Unlike with `get`, no borrow is held afterwards, e.g. across an `.await`.
+    pub fn get_cloned(&self) -> Result<T>
+    where T: Clone {
+        self.read(T::clone)
+    }
+    /// Like [`read`](Cached::read), but also holds the advisory lock shared while the closure runs,
+    /// if locking is enabled
+    ///
+    /// The cache file is checked once, and other processes using the lock can't change it
+    /// until the closure returns, so all the reads done by the closure see the same consistent value.
+    pub fn locked_read<R, F: FnOnce(&T) -> R>(&self, f: F) -> Result<R> {
+        let lock = self.read_lock()?;
+        self.write_if_due()?;
+        self.refresh()?;
+        let r = f(&*self.borrow()?);
+        drop(lock);
+        Ok(r)
+    }
+    /// Get a mutable reference to the inner value, after bringing it up to date like [`get`](Cached::get)
+    ///
+    /// **Changes made through this reference are not saved automatically.** They're only saved by
+    /// [`save_now`](Cached::save_now), [`save_if_dirty`](Cached::save_if_dirty), the next mutation or
+    /// [`flush_on_drop`](CachedBuilder::flush_on_drop), and are lost if the cache file is reloaded first.
+    /// Prefer [`with_mut`](Cached::with_mut) or [`edit`](Cached::edit), which save automatically.
+    pub fn borrow_mut_raw(&self) -> Result<RefMut<'_, T>> {
         self.check_load()?;
-        let r = f(self.inner.get_mut());
-        self.save()?;
+        self.borrow_mut()
+    }
+    /// Applies the given closure to a mutable reference to the inner value
+    /// and automatically saves the state to the cache file afterwards,
+    /// unless the closure left the serialized value unchanged
+    ///
+    /// If saves are debounced, the save is only scheduled.
+    ///
+    /// The cache file is reloaded first if it has changed, so the closure is applied on top of
+    /// another process's changes, merging with them. Any unsaved changes to the inner value are combined
+    /// with the reloaded value by [`Cache::merge`]. Use [`do_mut_no_reload`](Cached::do_mut_no_reload)
+    /// to overwrite them with the in-memory value instead.
+    ///
+    /// If the save fails after the closure was run, the closure's result is still
+    /// available from the error, see [`MutError::Unsaved`].
+    #[inline]
+    pub fn do_mut<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> StdResult<R, MutError<R>> {
+        self.with_mut(f)
+    }
+    /// Like [`do_mut`](Cached::do_mut), but without reloading the cache file before running the closure
+    ///
+    /// The closure is applied to the in-memory value as it is, which is then saved
+    /// even if the closure didn't change it, so the last writer wins: any changes
+    /// made to the file by another process since it was last loaded are overwritten.
+    ///
+    /// If saves are debounced, the save is only scheduled.
+    pub fn do_mut_no_reload<R, F: FnOnce(&mut T) -> R>(&mut self, f: F) -> StdResult<R, MutError<R>> {
+        let lock = self.lock().map_err(MutError::NotRun)?;
+        self.write_if_due().map_err(MutError::NotRun)?;
+        let r = f(&mut *self.borrow_mut().map_err(MutError::NotRun)?);
+        let saved = match self.postponed_until() {
+            _ if self.autosave_paused.get() => Ok(()),
+            Some(deadline) => {
+                self.save_deadline.set(Some(deadline));
+                Ok(())
+            }
+            None => self.write(),
+        };
+        drop(lock);
+        match saved {
+            Ok(()) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Like `do_mut` but only needs a shared reference,
+    /// so it can be used when the instance is shared through an `Rc`
+    ///
+    /// Fails with [`CacheError::Busy`] if the inner value is currently borrowed.
+    pub fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> StdResult<R, MutError<R>> {
+        // The whole read-modify-write is done under one lock
+        let lock = self.lock().map_err(MutError::NotRun)?;
+        self.write_if_due().and_then(|()| self.refresh()).map_err(MutError::NotRun)?;
+        let r = f(&mut *self.borrow_mut().map_err(MutError::NotRun)?);
+        let saved = self.write_after_mut();
+        drop(lock);
+        match saved {
+            Ok(()) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Like [`with_mut`](Cached::with_mut), but also tells what the closure changed, e.g. for showing
+    /// which settings were changed when saving them
+    ///
+    /// The inner value is cloned before running the closure, and the mutated value is compared to it,
+    /// with the changed keys given by [`Cache::diff`].
+    pub fn do_mut_diff<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> StdResult<(R, Diff), MutError<(R, Diff)>>
+    where T: Clone + PartialEq {
+        self.with_mut(|inner| {
+            let old = inner.clone();
+            let r = f(inner);
+            (r, Diff::between(&old, inner))
+        })
+    }
+    /// Like [`with_mut`](Cached::with_mut), but only saves if `should_save` returns `true`
+    /// for the mutated inner value
+    ///
+    /// Otherwise the changes are kept in memory unsaved, until the next save.
+    pub fn do_mut_if<R, F, P>(&self, f: F, should_save: P) -> StdResult<R, MutError<R>>
+    where
+        F: FnOnce(&mut T) -> R,
+        P: FnOnce(&T) -> bool,
+    {
+        let lock = self.lock().map_err(MutError::NotRun)?;
+        self.write_if_due().and_then(|()| self.refresh()).map_err(MutError::NotRun)?;
+        let (r, save) = {
+            let mut inner = self.borrow_mut().map_err(MutError::NotRun)?;
+            let r = f(&mut inner);
+            (r, should_save(&inner))
+        };
+        let saved = if save { self.write_after_mut() } else { Ok(()) };
+        drop(lock);
+        match saved {
+            Ok(()) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Like [`with_mut`](Cached::with_mut), but for closures that can fail
+    ///
+    /// If the closure returns `Ok`, the inner value is saved. If it returns `Err`,
+    /// any changes it made are rolled back by restoring a copy taken beforehand,
+    /// and its error is returned without saving.
+    ///
+    /// Fails with [`CacheError::Busy`] if the inner value is currently borrowed.
+    pub fn try_mut<R, E, F>(&self, f: F) -> StdResult<R, E>
+    where
+        T: Clone,
+        E: From<CacheError>,
+        F: FnOnce(&mut T) -> StdResult<R, E>,
+    {
+        let lock = self.lock()?;
+        self.write_if_due().and_then(|()| self.refresh())?;
+        let r = {
+            let mut inner = self.borrow_mut()?;
+            let before = inner.clone();
+            let r = f(&mut inner);
+            if r.is_err() {
+                *inner = before;
+            }
+            r
+        };
+        if r.is_ok() {
+            self.write_after_mut()?;
+        }
+        drop(lock);
+        r
+    }
+    /// Runs the closure on a copy of the inner value, which replaces the inner value and is saved
+    /// only if the closure returns `Ok`
+    ///
+    /// This requires `T: Clone` for taking the copy. Unlike [`try_mut`](Cached::try_mut),
+    /// the inner value is also left untouched if the closure panics, since it is never
+    /// borrowed while the closure runs. This also means the closure may use the instance itself,
+    /// e.g. with [`peek`](Cached::peek) to see the value from before the transaction.
+    pub fn transaction<R, F: FnOnce(&mut T) -> Result<R>>(&self, f: F) -> Result<R>
+    where T: Clone {
+        let lock = self.lock()?;
+        self.write_if_due().and_then(|()| self.refresh())?;
+        let mut value = self.borrow()?.clone();
+        let r = f(&mut value)?;
+        *self.borrow_mut()? = value;
+        self.write_after_mut()?;
+        drop(lock);
         Ok(r)
     }
+    /// Extends the inner collection with the items of the iterator and saves it once afterwards,
+    /// like [`with_mut`](Cached::with_mut)
+    pub fn extend_and_save<A, I: IntoIterator<Item = A>>(&self, iter: I) -> Result<()>
+    where T: Extend<A> {
+        self.with_mut(|inner| inner.extend(iter)).map_err(MutError::into_error)
+    }
+    /// Replaces the inner value with the given one and saves it, like [`with_mut`](Cached::with_mut)
+    ///
+    /// The cache file isn't loaded first, since its contents are replaced anyway.
+    pub fn set(&self, value: T) -> Result<()> {
+        self.replace(value).map(drop).map_err(MutError::into_error)
+    }
+    /// Replaces the inner value with the given one and saves it right away, even if saves are debounced
+    ///
+    /// Unlike [`set`](Cached::set), this always writes the cache file,
+    /// and any pending debounced save is replaced by this one.
+    pub fn reset_to(&self, value: T) -> Result<()> {
+        let _lock = self.lock()?;
+        *self.borrow_mut()? = value;
+        self.untouched.set(false);
+        self.write()
+    }
+    /// Replaces the inner value with the given one and saves it, returning the old value
+    ///
+    /// If the save fails, the old value is still available from the error, see [`MutError::Unsaved`].
+    pub fn replace(&self, value: T) -> StdResult<T, MutError<T>> {
+        let lock = self.lock().map_err(MutError::NotRun)?;
+        let old = mem::replace(&mut *self.borrow_mut().map_err(MutError::NotRun)?, value);
+        self.untouched.set(false);
+        let saved = self.write_after_mut();
+        drop(lock);
+        match saved {
+            Ok(()) => Ok(old),
+            Err(e) => Err(MutError::Unsaved(old, e)),
+        }
+    }
+    /// Returns a guard giving mutable access to the inner value,
+    /// so that several mutations can be done with only a single save at the end
+    ///
+    /// The value is saved when the guard is committed with [`EditGuard::commit`] or dropped.
+    ///
+    /// Fails with [`CacheError::Busy`] if the inner value is currently borrowed.
+    pub fn edit(&self) -> Result<EditGuard<'_, T, S>> {
+        self.check_load()?;
+        Ok(EditGuard::new(self, self.borrow_mut()?))
+    }
+    /// Consumes the instance, saving the inner value if it has changed since it was last
+    /// loaded or saved (such as when a debounced save is pending)
+    ///
+    /// Unlike dropping the instance, this reports whether saving failed,
+    /// so this should be used whenever the changes must be persisted.
+    pub fn flush(self) -> Result<()> {
+        let saved = self.save_if_dirty();
+        // Don't retry on drop
+        drop(self.into_inner());
+        saved.map(|_| ())
+    }
     /// Consumes the instance, and returns the inner `T`.
-    pub fn into_inner(self) -> T {
-        let Cached{inner, ..} = self;
-        inner.into_inner()
+    ///
+    /// A pending debounced save is not done, and neither is the save of
+    /// [`flush_on_drop`](CachedBuilder::flush_on_drop), since the value is moved out instead of dropped.
+    pub fn into_inner(mut self) -> T {
+        self.inner.take().expect("inner value has been moved out").into_inner()
+    }
+}
+
+impl<T: Cache, S: Storage> Drop for Cached<T, S> {
+    /// Does the pending debounced save, if there is one,
+    /// or saves any unsaved changes with [`flush_on_drop`](CachedBuilder::flush_on_drop)
+    ///
+    /// This is only a best-effort safety net, since errors can't be returned from here,
+    /// so a failed save is only logged. Use [`Cached::flush`] to know that changes were persisted.
+    fn drop(&mut self) {
+        if self.inner.is_none() {
+            return;
+        }
+        let saved = if self.options.flush_on_drop {
+            self.save_if_dirty()
+        } else {
+            self.save_pending()
+        };
+        if let Err(_e) = saved {
+            debug!("failed to save {} on drop: {}", self.name(), _e);
+        }
+    }
+}
+
+/// What [`Cached::poll`] found when checking the cache file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum CacheEvent {
+    /// The cache file hasn't changed since it was last loaded or saved
+    Unchanged,
+    /// The cache file had changed and was loaded, having been modified at the given time
+    Reloaded(SystemTime),
+    /// The cache file doesn't exist
+    FileMissing,
+}
+
+/// Object-safe operations on a cached value of any type,
+/// e.g. for keeping many caches in a `Vec<Box<dyn Flushable>>` to save them all at shutdown
+pub trait Flushable {
+    /// Writes the in-memory value to its cache file, regardless of whether it has been changed
+    fn save_now(&self) -> Result<()>;
+    /// Re-reads the cache file, discarding any unsaved changes to the in-memory value
+    fn reload(&self) -> Result<()>;
+}
+
+impl<T: Cache, S: Storage> Flushable for Cached<T, S> {
+    #[inline]
+    fn save_now(&self) -> Result<()> {
+        Cached::save_now(self)
+    }
+    #[inline]
+    fn reload(&self) -> Result<()> {
+        Cached::reload(self)
     }
 }
 
+impl<T: Cache> Flushable for sync::SyncCached<T> {
+    #[inline]
+    fn save_now(&self) -> Result<()> {
+        sync::SyncCached::save_now(self)
+    }
+    #[inline]
+    fn reload(&self) -> Result<()> {
+        sync::SyncCached::reload(self)
+    }
+}
+
+#[cfg(any(feature = "serde", feature = "bincode"))]
+#[doc(hidden)]
+/// Used by the code generated by the derive macro
+pub mod __private {
+    pub use serde;
+    #[cfg(feature = "serde")]
+    pub use serde_json;
+    #[cfg(feature = "bincode")]
+    pub use bincode;
+}
+
 /// Trait for the functions on how the cache is saved and loaded
 pub trait Cache: Sized {
     /// Write data such that if reading the data would yield the same structure 
-    fn save<W: Write>(&self, write: W) -> Result<()>;
+    fn save<W: Write>(&self, write: W) -> io::Result<()>;
+    /// Write data to be stored in the file at the given path
+    ///
+    /// This is called instead of [`save`](Cache::save) when the data is stored in a file,
+    /// so that the format can depend on the path, such as on its extension.
+    /// The default calls `save`.
+    fn save_to<W: Write>(&self, write: W, path: &Path) -> io::Result<()> {
+        let _ = path;
+        self.save(write)
+    }
     /// Load data that corresponds to the way it's saved
-    fn load<R: Read>(reader: R) -> Result<Self>;
-}
\ No newline at end of file
+    fn load<R: Read>(reader: R) -> io::Result<Self>;
+    /// Load data from a buffered reader, which is what [`Cached`] passes
+    ///
+    /// This is useful for line-based formats. The default calls [`load`](Cache::load).
+    fn load_buf<R: BufRead>(reader: R) -> io::Result<Self> {
+        Self::load(reader)
+    }
+    /// Load data stored in the file at the given path
+    ///
+    /// The reader reads the file, but the path can be used to find other files next to it,
+    /// such as when data is split across several files.
+    /// This is called instead of [`load_buf`](Cache::load_buf) when the data is stored in a file.
+    /// The default calls `load_buf`.
+    fn load_from<R: BufRead>(reader: R, path: &Path) -> io::Result<Self> {
+        let _ = path;
+        Self::load_buf(reader)
+    }
+    /// Write only what has changed since the value was `since`, appending to data saved from `since`
+    ///
+    /// This is only used if appending is enabled with [`CachedBuilder::append`].
+    /// The default saves the whole value, which only works for formats where that can be appended.
+    fn append<W: Write>(&self, write: W, since: &Self) -> io::Result<()> {
+        let _ = since;
+        self.save(write)
+    }
+    /// Whether a value that was just loaded should be saved again right away,
+    /// such as when it was migrated from an older format (see [`Versioned`](formats::Versioned))
+    ///
+    /// Defaults to `false`.
+    fn needs_rewrite(&self) -> bool {
+        false
+    }
+    /// Whether loading is only sound if the stored data is replaced by saves rather than changed in place,
+    /// which makes [`Cached`] refuse appending and writing in place (see [`Mapped`](formats::Mapped))
+    ///
+    /// Defaults to `false`.
+    fn needs_replacing_saves() -> bool
+    where Self: Sized {
+        false
+    }
+    /// Combines the value with one loaded from the stored data after it was changed by someone else,
+    /// while this value has unsaved changes
+    ///
+    /// This lets concurrent changes from several processes be kept, such as by taking the union of sets,
+    /// instead of the unsaved changes being discarded. The merged value is saved with the next save.
+    /// The default replaces the value with the loaded one.
+    fn merge(&mut self, disk: Self) {
+        *self = disk;
+    }
+    /// The keys whose values differ from those of `old`, for map-shaped types, see [`Cached::do_mut_diff`]
+    ///
+    /// The default gives none, so only whether the value changed is told.
+    fn diff(&self, old: &Self) -> Vec<String> {
+        let _ = old;
+        Vec::new()
+    }
+    /// Roughly how many bytes the value takes up in memory, including what it owns on the heap,
+    /// see [`Cached::memory_estimate`]
+    ///
+    /// The default gives 0, for when memory use isn't tracked.
+    fn estimated_size(&self) -> usize {
+        0
+    }
+}
+
+/// Extension of [`Cache`] for map-shaped types, allowing only some of the keys to be loaded
+///
+/// Used by [`Cached::get_partial`].
+pub trait PartialCache: Cache {
+    /// Removes all entries except the ones with the given keys
+    fn retain_keys(&mut self, keys: &[&str]);
+    /// Load only the entries with the given keys
+    ///
+    /// Implement this for formats that can skip the other entries without parsing them.
+    /// The default loads everything and then calls [`retain_keys`](PartialCache::retain_keys).
+    fn load_keys<R: BufRead>(reader: R, keys: &[&str]) -> io::Result<Self> {
+        let mut value = Self::load_buf(reader)?;
+        value.retain_keys(keys);
+        Ok(value)
+    }
+}
+
+/// Extension of [`Cache`] for collection-shaped types, allowing the records to be visited one at a time
+///
+/// Used by [`Cached::for_each_record`].
+pub trait StreamingCache: Cache {
+    /// The type of the records in the collection
+    type Record;
+    /// Splits the value into its records
+    fn into_records(self) -> impl Iterator<Item = Self::Record>;
+    /// Load the records one by one, calling the visitor with each of them
+    ///
+    /// Implement this for formats that can be read a record at a time, so that the whole collection
+    /// is never in memory at once. The default loads everything and then calls
+    /// [`into_records`](StreamingCache::into_records).
+    fn load_streaming<R: BufRead, F: FnMut(Self::Record)>(reader: R, visitor: F) -> io::Result<()> {
+        Self::load_buf(reader)?.into_records().for_each(visitor);
+        Ok(())
+    }
+}
+
+/// Alternative to [`Cache`] for types that need some context to be saved and loaded,
+/// such as a key or a symbol table that isn't part of the value itself
+///
+/// Used by [`Cached::with_context`], which keeps the context.
+pub trait ContextCache: Sized + 'static {
+    /// The context passed to saves and loads
+    type Context: Send + Sync + 'static;
+    /// Write data such that loading it with the same context would yield the same structure
+    fn save_with<W: Write>(&self, write: W, context: &Self::Context) -> io::Result<()>;
+    /// Load data that corresponds to the way it's saved with the context
+    fn load_with<R: Read>(reader: R, context: &Self::Context) -> io::Result<Self>;
+}
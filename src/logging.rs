@@ -0,0 +1,48 @@
+//! Logging through the `log` crate when the `log` feature is enabled
+//!
+//! Without the feature, the macros only type check their arguments.
+
+use std::{
+    fmt::{self, Display},
+    path::Path,
+};
+
+#[cfg(feature = "log")]
+macro_rules! debug {
+    ($($arg:tt)*) => { ::log::debug!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+#[cfg(feature = "log")]
+macro_rules! trace {
+    ($($arg:tt)*) => { ::log::trace!($($arg)*) };
+}
+
+#[cfg(not(feature = "log"))]
+macro_rules! trace {
+    ($($arg:tt)*) => {
+        if false {
+            let _ = format_args!($($arg)*);
+        }
+    };
+}
+
+/// Displays the path of a storage, for storages that have one
+pub(crate) struct StorageName<'a>(pub Option<&'a Path>);
+
+impl Display for StorageName<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.0 {
+            Some(path) => path.display().fmt(f),
+            None => f.write_str("<storage>"),
+        }
+    }
+}
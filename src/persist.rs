@@ -0,0 +1,313 @@
+//! Storage operations shared between the cached types
+
+use std::{
+    cell::Cell,
+    io::{self, BufReader, BufWriter, Read, Write},
+    fmt::{self, Debug},
+    path::Path,
+    sync::atomic::{AtomicBool, Ordering},
+    time::SystemTime,
+};
+
+use crate::{
+    Cache, CacheError, Result, error,
+    hashing::{HashReader, HashWriter},
+    storage::{Storage, StorageMetadata, StorageWriter},
+};
+
+type SaveFn<T> = dyn Fn(&T, &mut dyn Write) -> io::Result<()> + Send;
+type LoadFn<T> = dyn Fn(&mut dyn Read) -> io::Result<T> + Send;
+
+/// Closures that save and load the value instead of its [`Cache`] implementation
+pub(crate) struct Codec<T> {
+    pub save: Box<SaveFn<T>>,
+    pub load: Box<LoadFn<T>>,
+}
+
+impl<T> Debug for Codec<T> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("Codec")
+    }
+}
+
+/// Saves the value with the codec if there is one, for data stored at the given path (if any)
+fn save<T: Cache>(value: &T, codec: Option<&Codec<T>>, path: Option<&Path>, writer: &mut dyn Write) -> io::Result<()> {
+    match (codec, path) {
+        (Some(codec), _) => (codec.save)(value, writer),
+        (None, Some(path)) => value.save_to(writer, path),
+        (None, None) => value.save(writer),
+    }
+}
+
+/// Gets the metadata of the stored data, or `None` if nothing is stored yet
+pub(crate) fn stat<S: Storage>(storage: &S) -> Result<Option<StorageMetadata>> {
+    storage.metadata().map_err(CacheError::Metadata)
+}
+
+/// Gets the metadata of the stored data, which has to exist
+pub(crate) fn existing_stat<S: Storage>(storage: &S) -> Result<StorageMetadata> {
+    stat(storage)?.ok_or_else(|| {
+        CacheError::Metadata(io::Error::new(io::ErrorKind::NotFound, "nothing is stored"))
+    })
+}
+
+/// Whether data with the given metadata has changed since it had the given modification time and length
+pub(crate) fn has_changed(
+    metadata: &StorageMetadata,
+    last_modified: SystemTime,
+    last_len: u64,
+    last_file_id: Option<(u64, u64)>,
+) -> bool {
+    // Any difference counts, since the modification time goes backwards when an older copy is restored.
+    // The length is compared as well, since changes within the granularity
+    // of the modification time won't change it, and so is the file itself,
+    // since a file moved into place can keep an older modification time
+    last_modified != metadata.modified || last_len != metadata.len || last_file_id != metadata.file_id
+}
+
+/// Capacity of the buffers used for loading and saving when none is set, which is that of the standard library
+const DEFAULT_BUFFER_SIZE: usize = 8 * 1024;
+
+/// Loads a value through a buffer of the given size from the reader of data stored at the given path (if any),
+/// also returning the hash of the bytes that were read
+pub(crate) fn read_from<T: Cache, R: Read>(
+    reader: R,
+    path: Option<&Path>,
+    codec: Option<&Codec<T>>,
+    buffer_size: Option<usize>,
+) -> Result<(T, u64)> {
+    let mut reader = buffered_reader(HashReader::new(reader), buffer_size);
+
+    let value = match (codec, path) {
+        (Some(codec), _) => (codec.load)(&mut reader),
+        (None, Some(path)) => T::load_from(&mut reader, path),
+        (None, None) => T::load_buf(&mut reader),
+    };
+    let value = match path {
+        Some(path) => value.map_err(error::with_path(path)),
+        None => value,
+    };
+    Ok((value.map_err(CacheError::Load)?, reader.get_ref().finish()))
+}
+
+/// Hashes the stored data as it is, without loading it
+pub(crate) fn stored_hash<S: Storage>(storage: &S) -> Result<u64> {
+    let mut reader = storage.read().map_err(CacheError::Open)?;
+    let mut hasher = HashWriter::new(io::sink());
+    io::copy(&mut reader, &mut hasher).map_err(CacheError::Load)?;
+    Ok(hasher.finish())
+}
+
+/// Loads the stored data, also returning the hash of the bytes that were read
+///
+/// Fails with [`CacheError::TooLarge`] if more than `max_size` bytes are read.
+pub(crate) fn read<T: Cache, S: Storage>(
+    storage: &S,
+    codec: Option<&Codec<T>>,
+    max_size: Option<u64>,
+    buffer_size: Option<usize>,
+) -> Result<(T, u64)> {
+    let reader = storage.read().map_err(CacheError::Open)?;
+    match max_size {
+        None => read_from(reader, storage.file_path(), codec, buffer_size),
+        Some(max) => {
+            let exceeded = Cell::new(false);
+            let reader = Limited { inner: reader, remaining: max, exceeded: &exceeded };
+            read_from(reader, storage.file_path(), codec, buffer_size)
+                .map_err(|e| if exceeded.get() { CacheError::TooLarge(max) } else { e })
+        }
+    }
+}
+
+/// Reader that fails once more than a limited number of bytes have been read,
+/// in case the data grows while it is being read
+struct Limited<'a, R> {
+    inner: R,
+    remaining: u64,
+    exceeded: &'a Cell<bool>,
+}
+
+impl<R: Read> Read for Limited<'_, R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        let n = self.inner.read(buf)?;
+        match self.remaining.checked_sub(n as u64) {
+            Some(remaining) => {
+                self.remaining = remaining;
+                Ok(n)
+            }
+            None => {
+                self.exceeded.set(true);
+                Err(io::Error::new(io::ErrorKind::InvalidData, "data is larger than the maximum size"))
+            }
+        }
+    }
+}
+
+/// Writes the value to the storage through a buffer of the given size,
+/// returning the new metadata and the hash of the bytes written
+///
+/// Fails with [`Interrupted`](io::ErrorKind::Interrupted) without committing if the cancellation flag gets set.
+pub(crate) fn write<T: Cache, S: Storage>(
+    storage: &S,
+    value: &T,
+    codec: Option<&Codec<T>>,
+    cancel: Option<&AtomicBool>,
+    buffer_size: Option<usize>,
+) -> Result<(StorageMetadata, u64)> {
+    write_teed(storage, value, codec, cancel, buffer_size, false, io::sink()).map(|(metadata, hash, _)| (metadata, hash))
+}
+
+/// Like [`write`], but also passing the bytes written to `tee`, which is returned afterwards,
+/// and with the space for the data reserved up front if `preallocate` is set
+pub(crate) fn write_teed<T: Cache, S: Storage, D: Write>(
+    storage: &S,
+    value: &T,
+    codec: Option<&Codec<T>>,
+    cancel: Option<&AtomicBool>,
+    buffer_size: Option<usize>,
+    preallocate: bool,
+    tee: D,
+) -> Result<(StorageMetadata, u64, D)> {
+    let cancelled = || cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed));
+    let mut storage_writer = storage.write().map_err(CacheError::Save)?;
+    if preallocate {
+        let len = serialized_len(storage, value, codec)?;
+        storage_writer.preallocate(len).map_err(CacheError::Save)?;
+    }
+    let mut writer = HashWriter::new(Tee {
+        writer: Cancellable {
+            writer: buffered_writer(storage_writer, buffer_size),
+            cancel,
+        },
+        tee,
+    });
+    let saved = save(value, codec, storage.file_path(), &mut writer);
+    // Also checked after the last write, so that a cancelled save is never committed
+    let saved = if cancelled() { Err(io::Error::other(CANCELLED)) } else { saved };
+    saved.map_err(|source| CacheError::Write {
+        // The serializer might not preserve the error kind
+        source: if cancelled() { io::Error::new(io::ErrorKind::Interrupted, CANCELLED) } else { source },
+        bytes_written: writer.len(),
+    })?;
+
+    let hash = writer.finish();
+    let Tee { writer, tee } = writer.into_inner();
+    let metadata = unbuffer(writer.writer)?.commit().map_err(CacheError::Save)?;
+    Ok((metadata, hash, tee))
+}
+
+/// Writes the value to the storage through a buffer of the given size, and prepares it
+/// without committing, returning the writer to commit and the hash of the bytes written
+pub(crate) fn prepare<'a, T: Cache, S: Storage>(
+    storage: &'a S,
+    value: &T,
+    codec: Option<&Codec<T>>,
+    buffer_size: Option<usize>,
+) -> Result<(Box<dyn StorageWriter + 'a>, u64)> {
+    let mut writer = HashWriter::new(buffered_writer(storage.write().map_err(CacheError::Save)?, buffer_size));
+    save(value, codec, storage.file_path(), &mut writer)
+        .map_err(|source| CacheError::Write { source, bytes_written: writer.len() })?;
+
+    let hash = writer.finish();
+    let mut writer = unbuffer(writer.into_inner())?;
+    writer.prepare().map_err(CacheError::Save)?;
+    Ok((writer, hash))
+}
+
+/// Writer that also writes the bytes accepted by its writer to another one
+struct Tee<W, D> {
+    writer: W,
+    tee: D,
+}
+
+impl<W: Write, D: Write> Write for Tee<W, D> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        let n = self.writer.write(buf)?;
+        self.tee.write_all(&buf[..n])?;
+        Ok(n)
+    }
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+const CANCELLED: &str = "save was cancelled";
+
+/// Writer that fails once the cancellation flag is set, so that a long save is stopped partway
+///
+/// The error isn't [`Interrupted`](io::ErrorKind::Interrupted), since `write_all` would just retry.
+struct Cancellable<'a, W> {
+    writer: W,
+    cancel: Option<&'a AtomicBool>,
+}
+
+impl<W: Write> Write for Cancellable<'_, W> {
+    fn write(&mut self, buf: &[u8]) -> io::Result<usize> {
+        match self.cancel {
+            Some(cancel) if cancel.load(Ordering::Relaxed) => Err(io::Error::other(CANCELLED)),
+            _ => self.writer.write(buf),
+        }
+    }
+    #[inline]
+    fn flush(&mut self) -> io::Result<()> {
+        self.writer.flush()
+    }
+}
+
+/// Writes the bytes to the storage as they are, returning the new metadata and the hash of the bytes
+pub(crate) fn write_raw<S: Storage>(storage: &S, bytes: &[u8]) -> Result<(StorageMetadata, u64)> {
+    let mut writer = HashWriter::new(storage.write().map_err(CacheError::Save)?);
+    writer.write_all(bytes).map_err(CacheError::Save)?;
+
+    let hash = writer.finish();
+    let metadata = writer.into_inner().commit().map_err(CacheError::Save)?;
+    Ok((metadata, hash))
+}
+
+/// Appends what has changed since `since` to the storage through a buffer of the given size,
+/// returning the new metadata
+pub(crate) fn append<T: Cache, S: Storage>(storage: &S, value: &T, since: &T, buffer_size: Option<usize>) -> Result<StorageMetadata> {
+    let mut writer = buffered_writer(storage.append().map_err(CacheError::Save)?, buffer_size);
+    value.append(&mut writer, since).map_err(CacheError::Save)?;
+    unbuffer(writer)?.commit().map_err(CacheError::Save)
+}
+
+/// Buffers the reader with the given capacity, or the default one
+pub(crate) fn buffered_reader<R: Read>(reader: R, buffer_size: Option<usize>) -> BufReader<R> {
+    BufReader::with_capacity(buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE), reader)
+}
+
+/// Buffers the writer with the given capacity, or the default one
+fn buffered_writer<W: Write>(writer: W, buffer_size: Option<usize>) -> BufWriter<W> {
+    BufWriter::with_capacity(buffer_size.unwrap_or(DEFAULT_BUFFER_SIZE), writer)
+}
+
+/// Flushes the buffered writer, so that errors are caught before the data is committed
+fn unbuffer<W: Write>(writer: BufWriter<W>) -> Result<W> {
+    writer.into_inner().map_err(|e| CacheError::Save(e.into_error()))
+}
+
+/// The hash of empty stored data
+pub(crate) fn empty_hash() -> u64 {
+    HashWriter::new(io::sink()).finish()
+}
+
+/// Serializes the value to the writer as it would be stored, without touching the storage
+pub(crate) fn dump<T: Cache, S: Storage>(storage: &S, value: &T, codec: Option<&Codec<T>>, writer: &mut dyn Write) -> Result<()> {
+    save(value, codec, storage.file_path(), writer).map_err(CacheError::Save)
+}
+
+/// The length in bytes of the form the value would be serialized in for the storage, without writing it anywhere
+pub(crate) fn serialized_len<T: Cache, S: Storage>(storage: &S, value: &T, codec: Option<&Codec<T>>) -> Result<u64> {
+    let mut counter = HashWriter::new(io::sink());
+    save(value, codec, storage.file_path(), &mut counter).map_err(CacheError::Save)?;
+    Ok(counter.len())
+}
+
+/// Hashes the form the value would be serialized in for the storage, without writing it anywhere
+pub(crate) fn hash<T: Cache, S: Storage>(storage: &S, value: &T, codec: Option<&Codec<T>>) -> Result<u64> {
+    let mut hasher = HashWriter::new(io::sink());
+    save(value, codec, storage.file_path(), &mut hasher).map_err(CacheError::Save)?;
+    Ok(hasher.finish())
+}
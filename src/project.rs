@@ -0,0 +1,59 @@
+use std::{
+    cell::Ref,
+    fmt,
+    result::Result as StdResult,
+};
+
+use crate::{Cache, Cached, MutError, Result, storage::{FileStorage, Storage}};
+
+/// A view of a part of the inner value of a [`Cached`], made with [`Cached::project`]
+///
+/// The part is read and written with the closures given when making it, while the whole inner value
+/// is what is loaded and saved. This lets code that only cares about one part of a larger cache file
+/// work with just that part.
+pub struct ProjectedCached<'a, T: Cache, G, P, S: Storage = FileStorage> {
+    cached: &'a Cached<T, S>,
+    get: G,
+    set: P,
+}
+
+impl<'a, T: Cache, S: Storage, U, G, P> ProjectedCached<'a, T, G, P, S>
+where
+    G: Fn(&T) -> &U,
+    P: Fn(&mut T, U),
+{
+    #[inline]
+    pub(crate) fn new(cached: &'a Cached<T, S>, get: G, set: P) -> Self {
+        ProjectedCached { cached, get, set }
+    }
+    #[inline]
+    /// The instance the part is of
+    pub fn cached(&self) -> &'a Cached<T, S> {
+        self.cached
+    }
+    /// Get a reference to the part, like [`Cached::get`]
+    pub fn get(&self) -> Result<Ref<'a, U>> {
+        self.cached.get().map(|inner| Ref::map(inner, |inner| (self.get)(inner)))
+    }
+    /// Replaces the part with the given value and saves the whole inner value, like [`Cached::with_mut`]
+    pub fn set(&self, value: U) -> Result<()> {
+        self.cached.with_mut(|inner| (self.set)(inner, value)).map_err(MutError::into_error)
+    }
+    /// Applies the given closure to a copy of the part, which then replaces the part,
+    /// and saves the whole inner value, like [`Cached::with_mut`]
+    pub fn with_mut<R, F: FnOnce(&mut U) -> R>(&self, f: F) -> StdResult<R, MutError<R>>
+    where U: Clone {
+        self.cached.with_mut(|inner| {
+            let mut part = (self.get)(inner).clone();
+            let r = f(&mut part);
+            (self.set)(inner, part);
+            r
+        })
+    }
+}
+
+impl<T: Cache + fmt::Debug, G, P, S: Storage + fmt::Debug> fmt::Debug for ProjectedCached<'_, T, G, P, S> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("ProjectedCached").field("cached", self.cached).finish_non_exhaustive()
+    }
+}
@@ -0,0 +1,81 @@
+use std::{
+    cell::Ref,
+    path::{Path, PathBuf},
+    time::SystemTime,
+};
+
+use crate::{Cache, Cached, CachedBuilder, Result, storage::{FileStorage, Storage}};
+
+#[derive(Debug)]
+/// A cache that only reads the cache file and never writes to it
+///
+/// This only exposes the reading methods of [`Cached`], so nothing can be saved by accident,
+/// such as when the file is on a read-only filesystem.
+/// Note that [locking](CachedBuilder::locking) creates a lock file, so it shouldn't be enabled then.
+///
+/// Created with [`ReadOnlyCached::new`] or [`CachedBuilder::build_read_only`].
+pub struct ReadOnlyCached<T: Cache, S: Storage = FileStorage> {
+    cached: Cached<T, S>,
+}
+
+impl<T: Cache + Default> ReadOnlyCached<T> {
+    #[inline]
+    /// Make a new instance using the type's default function
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        Cached::builder(path).build_read_only()
+    }
+}
+
+impl<T: Cache> ReadOnlyCached<T> {
+    #[inline]
+    /// Make a new instance using a custom default function
+    pub fn new_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> Result<Self> {
+        CachedBuilder::new(default, FileStorage::new(path)).build_read_only()
+    }
+    #[inline]
+    /// The path of the cache file
+    pub fn path(&self) -> &Path {
+        self.cached.path()
+    }
+}
+
+impl<T: Cache, S: Storage> ReadOnlyCached<T, S> {
+    pub(crate) fn from_cached(cached: Cached<T, S>) -> Self {
+        ReadOnlyCached { cached }
+    }
+    #[inline]
+    /// The storage backing the cache
+    pub fn storage(&self) -> &S {
+        self.cached.storage()
+    }
+    #[inline]
+    /// The modification time of the cache file when it was last loaded
+    pub fn last_modified(&self) -> SystemTime {
+        self.cached.last_modified()
+    }
+    #[inline]
+    /// Get a reference to the inner type, see [`Cached::get`]
+    pub fn get(&self) -> Result<Ref<'_, T>> {
+        self.cached.get()
+    }
+    #[inline]
+    /// Get a reference to the inner value without checking the file, see [`Cached::peek`]
+    pub fn peek(&self) -> Ref<'_, T> {
+        self.cached.peek()
+    }
+    #[inline]
+    /// Applies the given closure to a reference to the inner value, see [`Cached::read`]
+    pub fn read<R, F: FnOnce(&T) -> R>(&self, f: F) -> Result<R> {
+        self.cached.read(f)
+    }
+    #[inline]
+    /// Re-reads the cache file even if it doesn't seem to have been modified, see [`Cached::reload`]
+    pub fn reload(&self) -> Result<()> {
+        self.cached.reload()
+    }
+    #[inline]
+    /// Consumes the instance, and returns the inner `T`.
+    pub fn into_inner(self) -> T {
+        self.cached.into_inner()
+    }
+}
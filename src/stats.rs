@@ -0,0 +1,89 @@
+use std::{
+    sync::atomic::{AtomicU64, Ordering},
+    time::Duration,
+};
+
+/// Counters of what a cache has done, see [`Cached::stats`](crate::Cached::stats)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct CacheStats {
+    /// How many times the cache file was loaded
+    pub loads: u64,
+    /// How many times the cache file was saved
+    pub saves: u64,
+    /// How many times the cache file was checked but didn't need to be loaded, since it hadn't changed
+    pub skipped_reloads: u64,
+    /// How many times loading the cache file failed
+    pub load_errors: u64,
+    /// How many times saving the cache file failed
+    pub save_errors: u64,
+}
+
+/// Counters that can be shared between threads
+#[derive(Debug, Default)]
+pub(crate) struct AtomicStats {
+    loads: AtomicU64,
+    saves: AtomicU64,
+    skipped_reloads: AtomicU64,
+    load_errors: AtomicU64,
+    save_errors: AtomicU64,
+}
+
+impl AtomicStats {
+    pub fn load<T, E>(&self, result: &Result<T, E>) {
+        let counter = if result.is_ok() { &self.loads } else { &self.load_errors };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn save<T, E>(&self, result: &Result<T, E>) {
+        let counter = if result.is_ok() { &self.saves } else { &self.save_errors };
+        counter.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn skipped_reload(&self) {
+        self.skipped_reloads.fetch_add(1, Ordering::Relaxed);
+    }
+    pub fn snapshot(&self) -> CacheStats {
+        CacheStats {
+            loads: self.loads.load(Ordering::Relaxed),
+            saves: self.saves.load(Ordering::Relaxed),
+            skipped_reloads: self.skipped_reloads.load(Ordering::Relaxed),
+            load_errors: self.load_errors.load(Ordering::Relaxed),
+            save_errors: self.save_errors.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// How often the locks of a [`SyncCached`](crate::sync::SyncCached) were contended,
+/// see [`SyncCached::contention_stats`](crate::sync::SyncCached::contention_stats)
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct ContentionStats {
+    /// How many times taking a lock for reading had to wait for a writer
+    pub blocked_reads: u64,
+    /// How many times taking a lock for writing had to wait for other readers or writers
+    pub blocked_writes: u64,
+    /// The total time spent waiting for locks
+    pub wait_time: Duration,
+}
+
+/// Contention counters that can be shared between threads
+#[derive(Debug, Default)]
+pub(crate) struct AtomicContention {
+    blocked_reads: AtomicU64,
+    blocked_writes: AtomicU64,
+    wait_nanos: AtomicU64,
+}
+
+impl AtomicContention {
+    pub fn blocked(&self, write: bool, waited: Duration) {
+        let counter = if write { &self.blocked_writes } else { &self.blocked_reads };
+        counter.fetch_add(1, Ordering::Relaxed);
+        self.wait_nanos.fetch_add(waited.as_nanos() as u64, Ordering::Relaxed);
+    }
+    pub fn snapshot(&self) -> ContentionStats {
+        ContentionStats {
+            blocked_reads: self.blocked_reads.load(Ordering::Relaxed),
+            blocked_writes: self.blocked_writes.load(Ordering::Relaxed),
+            wait_time: Duration::from_nanos(self.wait_nanos.load(Ordering::Relaxed)),
+        }
+    }
+}
@@ -0,0 +1,231 @@
+use std::{
+    io::{self, ErrorKind, Read, Write, Result},
+    fs::{self, File, OpenOptions},
+    path::{Path, PathBuf},
+    process,
+    sync::atomic::{AtomicUsize, Ordering},
+    time::SystemTime,
+};
+
+use sha2::{Digest, Sha256};
+
+use super::{Storage, StorageMetadata, StorageWriter, fs_metadata};
+use crate::error::with_path;
+
+/// Name of the pointer file naming the current blob
+const CURRENT: &str = "current";
+/// Name of the file locked while changing which blobs there are
+const LOCK: &str = ".lock";
+
+/// Stores each save in a directory as a blob named by the SHA-256 of its contents,
+/// with a `current` file naming the latest one
+///
+/// Saving the same data twice only stores it once, and blobs are never changed after they're written,
+/// so the previous saves are kept as snapshots, see [`snapshots`](ContentAddressed::snapshots),
+/// until they're removed with [`collect_garbage`](ContentAddressed::collect_garbage).
+/// The blob and then the `current` file are written to temporary files that are renamed into place,
+/// so neither is ever partially written. The directory is created when first saving.
+/// Committing a save and collecting garbage lock the directory, so that a blob isn't removed
+/// while a save is making it current, even by another process.
+#[derive(Debug, Clone)]
+pub struct ContentAddressed {
+    dir: Box<Path>,
+}
+
+impl ContentAddressed {
+    /// Makes a storage for the given directory
+    pub fn new<P: Into<PathBuf>>(dir: P) -> Self {
+        ContentAddressed { dir: dir.into().into_boxed_path() }
+    }
+    #[inline]
+    /// The directory of the blobs
+    pub fn dir(&self) -> &Path {
+        &self.dir
+    }
+    /// The path of the blob with the given hex-encoded SHA-256
+    pub fn blob_path(&self, digest: &str) -> PathBuf {
+        self.dir.join(digest)
+    }
+    /// The hex-encoded SHA-256 of the current blob, or `None` if nothing has been saved
+    pub fn current(&self) -> Result<Option<String>> {
+        let path = self.dir.join(CURRENT);
+        match fs::read_to_string(&path) {
+            Ok(digest) => Ok(Some(digest.trim().to_owned())),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(with_path(&path)(e)),
+        }
+    }
+    /// The hex-encoded SHA-256 of every blob with when it was last saved, newest first
+    pub fn snapshots(&self) -> Result<Vec<(SystemTime, String)>> {
+        let entries = match fs::read_dir(&self.dir) {
+            Ok(entries) => entries,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(Vec::new()),
+            Err(e) => return Err(with_path(&self.dir)(e)),
+        };
+        let mut snapshots = Vec::new();
+        for entry in entries {
+            let entry = entry.map_err(with_path(&self.dir))?;
+            let name = match entry.file_name().into_string() {
+                Ok(name) if is_digest(&name) => name,
+                _ => continue,
+            };
+            let modified = entry.metadata().and_then(|m| m.modified()).map_err(with_path(&entry.path()))?;
+            snapshots.push((modified, name));
+        }
+        snapshots.sort_unstable_by(|a, b| b.cmp(a));
+        Ok(snapshots)
+    }
+    /// Removes all blobs but the current one and the `keep` newest others, returning how many were removed
+    pub fn collect_garbage(&self, keep: usize) -> Result<usize> {
+        if !self.dir.exists() {
+            return Ok(0);
+        }
+        let _lock = self.lock_dir()?;
+        let current = self.current()?;
+        let old = self.snapshots()?.into_iter().filter(|(_, digest)| current.as_ref() != Some(digest));
+        let mut removed = 0;
+        for (_, digest) in old.skip(keep) {
+            let path = self.blob_path(&digest);
+            match fs::remove_file(&path) {
+                Err(ref e) if e.kind() == ErrorKind::NotFound => (),
+                r => r.map_err(with_path(&path))?,
+            }
+            removed += 1;
+        }
+        Ok(removed)
+    }
+    /// The path of a new temporary file in the directory, which no other write uses
+    fn temp(&self, name: &str) -> PathBuf {
+        static TEMPS: AtomicUsize = AtomicUsize::new(0);
+        self.dir.join(format!(".{}.tmp-{}-{}", name, process::id(), TEMPS.fetch_add(1, Ordering::Relaxed)))
+    }
+    /// Takes an exclusive advisory lock on the directory, which is released when the returned file is closed
+    fn lock_dir(&self) -> Result<File> {
+        let path = self.dir.join(LOCK);
+        let file = OpenOptions::new().write(true).create(true).truncate(false).open(&path).map_err(with_path(&path))?;
+        file.lock().map_err(with_path(&path))?;
+        Ok(file)
+    }
+    /// Makes the blob with the given digest current
+    fn set_current(&self, digest: &str) -> Result<()> {
+        let temp = self.temp(CURRENT);
+        fs::write(&temp, digest)?;
+        fs::rename(&temp, self.dir.join(CURRENT)).inspect_err(|_| drop(fs::remove_file(&temp)))
+    }
+}
+
+impl Storage for ContentAddressed {
+    /// The modification time is that of the `current` file, which changes with every save,
+    /// and the length is that of the current blob
+    fn metadata(&self) -> Result<Option<StorageMetadata>> {
+        let path = self.dir.join(CURRENT);
+        let pointer = match fs::metadata(&path) {
+            Ok(metadata) => fs_metadata(&metadata).map_err(with_path(&path))?,
+            Err(ref e) if e.kind() == ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(with_path(&path)(e)),
+        };
+        let digest = match self.current()? {
+            Some(digest) => digest,
+            // Removed in the meantime
+            None => return Ok(None),
+        };
+        let blob = self.blob_path(&digest);
+        let len = fs::metadata(&blob).map_err(with_path(&blob))?.len();
+        Ok(Some(StorageMetadata { len, ..pointer }))
+    }
+    fn read(&self) -> Result<Box<dyn Read + '_>> {
+        let digest = self.current()?.ok_or_else(|| {
+            with_path(&self.dir.join(CURRENT))(io::Error::new(ErrorKind::NotFound, "nothing has been saved"))
+        })?;
+        let blob = self.blob_path(&digest);
+        Ok(Box::new(File::open(&blob).map_err(with_path(&blob))?))
+    }
+    fn write(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        fs::create_dir_all(&self.dir).map_err(with_path(&self.dir))?;
+        let temp = self.temp("blob");
+        let file = OpenOptions::new().write(true).create(true).truncate(true).open(&temp).map_err(with_path(&temp))?;
+        Ok(Box::new(BlobWriter { storage: self, file: Some(file), temp: Some(temp), hasher: Sha256::new() }))
+    }
+    /// Removes the `current` file, keeping the blobs
+    fn delete(&self) -> Result<()> {
+        let path = self.dir.join(CURRENT);
+        match fs::remove_file(&path) {
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(()),
+            r => r.map_err(with_path(&path)),
+        }
+    }
+}
+
+struct BlobWriter<'a> {
+    storage: &'a ContentAddressed,
+    /// Closed once committed
+    file: Option<File>,
+    /// Taken once the temporary file has been renamed or removed when committing
+    temp: Option<PathBuf>,
+    hasher: Sha256,
+}
+
+impl BlobWriter<'_> {
+    fn temp(&self) -> &Path {
+        self.temp.as_deref().expect("writer has been committed")
+    }
+    fn finish(&mut self) -> Result<StorageMetadata> {
+        if let Some(mut file) = self.file.take() {
+            file.flush()?;
+        }
+        let digest: String = self.hasher.clone().finalize().iter().map(|b| format!("{:02x}", b)).collect();
+        let blob = self.storage.blob_path(&digest);
+        // Held until the blob is current, so that it isn't collected as garbage in between
+        let _lock = self.storage.lock_dir()?;
+        let temp = self.temp.take().expect("writer has been committed");
+        if blob.exists() {
+            // Already stored, so only mark it as saved again
+            fs::remove_file(&temp)?;
+            OpenOptions::new().write(true).open(&blob)?.set_modified(SystemTime::now())?;
+        } else if let Err(e) = fs::rename(&temp, &blob) {
+            self.temp = Some(temp);
+            return Err(e);
+        }
+        self.storage.set_current(&digest)?;
+        self.storage.metadata()?.ok_or_else(|| io::Error::new(ErrorKind::NotFound, "removed while saving"))
+    }
+}
+
+impl Write for BlobWriter<'_> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let file = self.file.as_mut().expect("writer has been committed");
+        let n = file.write(buf).map_err(with_path(self.temp.as_deref().expect("writer has been committed")))?;
+        self.hasher.update(&buf[..n]);
+        Ok(n)
+    }
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.file {
+            Some(file) => file.flush().map_err(with_path(self.temp())),
+            None => Ok(()),
+        }
+    }
+}
+
+impl StorageWriter for BlobWriter<'_> {
+    fn commit(mut self: Box<Self>) -> Result<StorageMetadata> {
+        let dir = self.storage.dir.clone();
+        self.finish().map_err(with_path(&dir))
+    }
+}
+
+impl Drop for BlobWriter<'_> {
+    /// Removes the temporary file if the blob wasn't committed
+    fn drop(&mut self) {
+        drop(self.file.take());
+        if let Some(temp) = self.temp.take() {
+            let _ = fs::remove_file(temp);
+        }
+    }
+}
+
+/// Whether a file name is that of a blob
+fn is_digest(name: &str) -> bool {
+    name.len() == 64 && name.bytes().all(|b| b.is_ascii_hexdigit())
+}
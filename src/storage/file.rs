@@ -0,0 +1,866 @@
+use std::{
+    io::{self, BufRead, BufReader, Cursor, Read, Seek, Write, Result},
+    fs::{self, File, OpenOptions, TryLockError},
+    path::{Path, PathBuf},
+    ffi::OsString,
+    process,
+    sync::{atomic::{AtomicUsize, Ordering}, mpsc::{self, RecvTimeoutError}},
+    thread,
+    time::{Duration, Instant, SystemTime},
+};
+#[cfg(feature = "watch")]
+use std::sync::OnceLock;
+
+use super::{Sidecar, Storage, StorageLock, StorageMetadata, StorageWriter};
+use crate::{error::with_path, hashing::{Crc32, crc32}};
+#[cfg(feature = "watch")]
+use super::watch::Watcher;
+
+/// How long to wait before first trying to take the lock again when there is a lock timeout
+const LOCK_POLL_INTERVAL: Duration = Duration::from_millis(1);
+/// The most to wait between tries, as the wait doubles
+const LOCK_POLL_MAX_INTERVAL: Duration = Duration::from_millis(50);
+
+/// Stores the data in a file, which is the default storage
+///
+/// The options set on [`CachedBuilder`](crate::CachedBuilder) for atomic writes, locking, backups
+/// and watching apply to this storage.
+#[derive(Debug)]
+pub struct FileStorage {
+    /// Where the data is saved
+    pub(crate) path: Box<Path>,
+    /// Where the data is loaded from while nothing has been saved to `path`
+    pub(crate) load_path: Option<Box<Path>>,
+    /// Other files that are loaded instead when they are newer
+    pub(crate) search_paths: Vec<Box<Path>>,
+    /// Whether to save through a temporary file that is renamed into place
+    pub(crate) atomic_writes: bool,
+    /// Whether operations are guarded by an advisory lock
+    pub(crate) locking: bool,
+    /// How long to wait for the advisory lock before failing
+    pub(crate) lock_timeout: Option<Duration>,
+    /// How many backups of previous contents to keep
+    pub(crate) backups: usize,
+    /// Whether saves are synced to disk before they are considered done
+    pub(crate) durable: bool,
+    /// Permissions to create files with on Unix
+    pub(crate) mode: Option<u32>,
+    /// Where temporary files for atomic writes are put instead of next to the file
+    pub(crate) temp_dir: Option<Box<Path>>,
+    /// Whether missing parent directories are created when writing
+    pub(crate) create_dirs: bool,
+    /// Whether the file recreated by an atomic write gets the permissions and owner of the old one
+    pub(crate) preserve_permissions: bool,
+    /// The schema version recorded in the sidecar metadata file, if one is kept
+    pub(crate) sidecar: Option<u32>,
+    /// Whether files with the extension of an enabled compression format are compressed
+    pub(crate) compression: bool,
+    /// How long reading the file or its metadata may take before failing
+    pub(crate) load_timeout: Option<Duration>,
+    /// Whether to watch the file for changes instead of always reading its metadata
+    #[cfg(feature = "watch")]
+    pub(crate) watch: bool,
+    /// Started on first use, so that errors can be reported
+    #[cfg(feature = "watch")]
+    watcher: OnceLock<Watcher>,
+}
+
+impl FileStorage {
+    /// Makes a storage for the file at the given path, with atomic writes enabled
+    pub fn new<P: Into<PathBuf>>(path: P) -> Self {
+        FileStorage {
+            path: path.into().into_boxed_path(),
+            load_path: None,
+            search_paths: Vec::new(),
+            atomic_writes: true,
+            locking: false,
+            lock_timeout: None,
+            backups: 0,
+            durable: false,
+            mode: None,
+            temp_dir: None,
+            create_dirs: false,
+            preserve_permissions: false,
+            sidecar: None,
+            compression: true,
+            load_timeout: None,
+            #[cfg(feature = "watch")]
+            watch: false,
+            #[cfg(feature = "watch")]
+            watcher: OnceLock::new(),
+        }
+    }
+    #[inline]
+    /// The path of the file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    #[inline]
+    /// The path of the file that is loaded while the file doesn't exist,
+    /// which is the file itself unless a separate one was set
+    pub fn load_path(&self) -> &Path {
+        self.load_path.as_deref().unwrap_or(&self.path)
+    }
+    /// Opens the file read-only
+    pub fn open(&self) -> Result<File> {
+        File::open(&self.path).map_err(with_path(&self.path))
+    }
+    /// Reads the sidecar metadata file `<path>.meta`, or gives `None` if it doesn't exist,
+    /// see [`CachedBuilder::sidecar`](crate::CachedBuilder::sidecar)
+    ///
+    /// This can be used to tell which schema version the file was saved with before loading it.
+    pub fn sidecar(&self) -> Result<Option<Sidecar>> {
+        let path = sidecar_path(&self.path);
+        Sidecar::read(&path).map_err(with_path(&path))
+    }
+    /// The file to load and its metadata, or `None` if none of the files exist
+    ///
+    /// This is the newest of the search paths and the file (or the load path if the file doesn't exist).
+    fn newest(&self) -> Result<Option<(&Path, StorageMetadata)>> {
+        let mut newest = match self.timed(&self.path, stat)? {
+            Some(metadata) => Some((&*self.path, metadata)),
+            None => match self.load_path.as_deref() {
+                Some(load_path) => self.timed(load_path, stat)?.map(|metadata| (load_path, metadata)),
+                None => None,
+            },
+        };
+        for path in &self.search_paths {
+            if let Some(metadata) = self.timed(path, stat)? {
+                if newest.is_none_or(|(_, newest)| metadata.modified > newest.modified) {
+                    newest = Some((path, metadata));
+                }
+            }
+        }
+        Ok(newest)
+    }
+    fn stat(&self) -> Result<Option<StorageMetadata>> {
+        Ok(self.newest()?.map(|(_, metadata)| metadata))
+    }
+    /// Calls `f` with the given path on a helper thread if there is a load timeout,
+    /// failing with [`TimedOut`](io::ErrorKind::TimedOut) if it doesn't return in time
+    ///
+    /// The helper thread is left running if it's blocked.
+    fn timed<R: Send + 'static>(&self, path: &Path, f: fn(&Path) -> Result<R>) -> Result<R> {
+        let timeout = match self.load_timeout {
+            Some(timeout) => timeout,
+            None => return f(path),
+        };
+        let (sender, receiver) = mpsc::sync_channel(1);
+        let path = path.to_owned();
+        thread::Builder::new()
+            .name("cached-struct-load".to_owned())
+            .spawn(move || drop(sender.send(f(&path))))?;
+        match receiver.recv_timeout(timeout) {
+            Ok(r) => r,
+            Err(RecvTimeoutError::Timeout) => Err(io::Error::new(io::ErrorKind::TimedOut, format!(
+                "didn't finish reading within {:?}", timeout,
+            ))),
+            Err(RecvTimeoutError::Disconnected) => Err(io::Error::other("reading panicked")),
+        }
+    }
+    /// Makes the temporary file for an atomic write, in the temporary directory if one is set
+    fn temp(&self) -> TempPath {
+        let path = match (&self.temp_dir, self.path.file_name()) {
+            (Some(dir), Some(name)) => temp_path(&dir.join(name)),
+            _ => temp_path(&self.path),
+        };
+        TempPath(Some(path))
+    }
+    /// Path of the `n`th backup of the file, counting from 1 for the newest
+    pub(crate) fn backup_path(&self, n: usize) -> PathBuf {
+        with_suffix(&self.path, &format!(".bak.{}", n))
+    }
+    /// Shifts the existing backups one step older and copies the file to the newest backup
+    ///
+    /// Does nothing if the file doesn't exist yet.
+    fn rotate_backups(&self) -> Result<()> {
+        if self.backups == 0 || !self.path.exists() {
+            return Ok(());
+        }
+        for n in (1..self.backups).rev() {
+            match fs::rename(self.backup_path(n), self.backup_path(n + 1)) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                r => r?,
+            }
+        }
+        fs::copy(&self.path, self.backup_path(1)).map(|_| ())
+    }
+    /// The modification times and paths of the existing backups, from the newest
+    ///
+    /// Stops at the first missing one, so that the `n`th entry is the `n`th backup.
+    pub(crate) fn backups(&self) -> Result<Vec<(SystemTime, PathBuf)>> {
+        let mut backups = Vec::new();
+        for n in 1.. {
+            let path = self.backup_path(n);
+            match stat(&path)? {
+                Some(metadata) => backups.push((metadata.modified, path)),
+                None => break,
+            }
+        }
+        Ok(backups)
+    }
+    /// Sets the modification time of the file to now without changing its contents,
+    /// returning its new metadata, or `None` if it doesn't exist
+    pub(crate) fn touch(&self) -> Result<Option<StorageMetadata>> {
+        let file = match OpenOptions::new().write(true).open(&self.path) {
+            Ok(file) => file,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+            Err(e) => return Err(e),
+        };
+        file.set_modified(SystemTime::now())?;
+        fs_metadata(&file.metadata()?).map(Some)
+    }
+    /// Appends the line to the audit log next to the file, flushing it right away
+    pub(crate) fn append_log(&self, line: &str) -> Result<()> {
+        let mut file = self.create(&with_suffix(&self.path, ".log"), OpenOptions::new().append(true))?;
+        writeln!(file, "{}", line)?;
+        file.flush()?;
+        if self.durable {
+            file.sync_data()?;
+        }
+        Ok(())
+    }
+    /// Moves the file and its sidecar to the given path, if it exists
+    pub(crate) fn move_to(&self, path: &Path) -> Result<()> {
+        if stat(&self.path)?.is_some() {
+            self.create_parent(path)?;
+            fs::rename(&self.path, path)?;
+            if self.sidecar.is_some() {
+                match fs::rename(sidecar_path(&self.path), sidecar_path(path)) {
+                    Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                    r => r?,
+                }
+            }
+        }
+        Ok(())
+    }
+    /// Makes the storage use the file at the given path from now on
+    pub(crate) fn set_path(&mut self, path: PathBuf) {
+        self.path = path.into_boxed_path();
+        #[cfg(feature = "watch")]
+        {
+            self.watcher = OnceLock::new();
+        }
+    }
+    /// Writes the sidecar of the file, through a temporary file with atomic writes
+    fn write_sidecar(&self, sidecar: Sidecar) -> Result<()> {
+        let path = sidecar_path(&self.path);
+        let temp = TempPath(Some(temp_path(&path)));
+        let target = if self.atomic_writes { temp.path() } else { &path };
+        let mut file = self.create(target, OpenOptions::new().write(true).truncate(true))?;
+        file.write_all(sidecar.to_json().as_bytes())?;
+        if self.durable {
+            file.sync_all()?;
+        }
+        drop(file);
+        if self.atomic_writes {
+            temp.persist(&path)?;
+            self.sync_dir()?;
+        }
+        Ok(())
+    }
+    /// The CRC-32 of the file as it is, resumed from its sidecar if that is still up to date
+    fn current_crc(&self) -> Result<Crc32> {
+        let len = match stat(&self.path)? {
+            Some(metadata) => metadata.len,
+            None => return Ok(Crc32::new()),
+        };
+        match Sidecar::read(&sidecar_path(&self.path))? {
+            Some(sidecar) if sidecar.len == len => Ok(Crc32::resume(sidecar.crc32)),
+            _ => self.file_crc(),
+        }
+    }
+    /// The CRC-32 of the file, reading all of it
+    fn file_crc(&self) -> Result<Crc32> {
+        let mut crc = Crc32::new();
+        io::copy(&mut File::open(&self.path)?, &mut crc)?;
+        Ok(crc)
+    }
+    /// Reads the file at the given path, checking it against its sidecar
+    ///
+    /// A mismatch is reported when reading from the returned reader, so that it's treated
+    /// like invalid data, for which loading is retried in case a save is in progress.
+    fn read_checked(&self, path: &Path, version: u32) -> Result<Box<dyn Read + '_>> {
+        let bytes = self.timed(path, |path| fs::read(path))?;
+        let checked = match Sidecar::read(&sidecar_path(path))? {
+            Some(sidecar) => sidecar.verify(version, bytes.len() as u64, crc32(&bytes)),
+            // Files saved before the sidecar was enabled don't have one yet
+            None => Ok(()),
+        };
+        match checked {
+            Ok(()) => Ok(Box::new(Cursor::new(bytes))),
+            Err(e) => Ok(Box::new(FailingReader(Some(e)))),
+        }
+    }
+    /// Atomically replaces the file with a copy of the `n`th backup
+    pub(crate) fn restore_backup(&self, n: usize) -> Result<()> {
+        let temp = self.temp();
+        fs::copy(self.backup_path(n), temp.path())?;
+        let file = File::open(temp.path())?;
+        self.copy_permissions(&file)?;
+        if self.durable {
+            file.sync_all()?;
+        }
+        drop(file);
+        temp.persist(&self.path)?;
+        if let Some(version) = self.sidecar {
+            // The backup's sidecar isn't kept, so its schema version is assumed to be the current one
+            let crc = self.file_crc()?.finish();
+            let len = fs::metadata(&self.path)?.len();
+            self.write_sidecar(Sidecar { version, len, crc32: crc, saved: SystemTime::now() })?;
+        }
+        self.sync_dir()
+    }
+    /// Gives the file that is about to replace the file the permissions of the file, if that is enabled
+    ///
+    /// On Unix, the owner and group are also copied, if the process is allowed to.
+    fn copy_permissions(&self, file: &File) -> Result<()> {
+        if !self.preserve_permissions {
+            return Ok(());
+        }
+        let metadata = match fs::metadata(&self.path) {
+            Ok(metadata) => metadata,
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => return Ok(()),
+            Err(e) => return Err(e),
+        };
+        file.set_permissions(metadata.permissions())?;
+        #[cfg(unix)]
+        {
+            use std::os::unix::fs::{MetadataExt, fchown};
+            match fchown(file, Some(metadata.uid()), Some(metadata.gid())) {
+                // Only privileged processes can give files away
+                Err(ref e) if e.kind() == io::ErrorKind::PermissionDenied => (),
+                r => r?,
+            }
+        }
+        Ok(())
+    }
+    /// Creates the parent directories of the given path if that is enabled
+    fn create_parent(&self, path: &Path) -> Result<()> {
+        match path.parent() {
+            Some(dir) if self.create_dirs && !dir.as_os_str().is_empty() => fs::create_dir_all(dir),
+            _ => Ok(()),
+        }
+    }
+    /// Opens the file at the given path for writing, creating it with the configured mode
+    ///
+    /// Its parent directories are created first if that is enabled.
+    fn create(&self, path: &Path, options: &mut OpenOptions) -> Result<File> {
+        self.create_parent(path)?;
+        #[cfg(unix)]
+        if let Some(mode) = self.mode {
+            use std::os::unix::fs::OpenOptionsExt;
+            options.mode(mode);
+        }
+        options.create(true).open(path)
+    }
+    /// Syncs the directory of the file, so that a rename into it is durable, if saves are durable
+    fn sync_dir(&self) -> Result<()> {
+        // Directories can't be opened as files on Windows, where renames are durable anyway
+        #[cfg(unix)]
+        if self.durable {
+            let dir = match self.path.parent() {
+                Some(dir) if !dir.as_os_str().is_empty() => dir,
+                _ => Path::new("."),
+            };
+            File::open(dir)?.sync_all()?;
+        }
+        Ok(())
+    }
+}
+
+impl FileStorage {
+    #[cfg(feature = "watch")]
+    /// Gets the metadata through the watcher, starting it on first use
+    fn watched_metadata(&self) -> Result<Option<StorageMetadata>> {
+        let watcher = match self.watcher.get() {
+            Some(watcher) => watcher,
+            None => {
+                let watcher = Watcher::new(&self.path)?;
+                self.watcher.get_or_init(|| watcher)
+            }
+        };
+        watcher.metadata(|| self.stat())
+    }
+    /// The compression format of the file at the given path, if compression is enabled
+    fn compression(&self, path: &Path) -> Option<Compression> {
+        if self.compression { Compression::of(path) } else { None }
+    }
+    /// Wraps the reader of the file at the given path in a decoder if it's compressed
+    fn decompress<'a>(&self, path: &Path, reader: Box<dyn Read + 'a>) -> Result<Box<dyn Read + 'a>> {
+        Ok(match self.compression(path) {
+            None => reader,
+            Some(compression) => match compression {
+                // Appending adds another gzip member or zstd frame
+                #[cfg(feature = "gzip")]
+                Compression::Gzip => Box::new(flate2::read::MultiGzDecoder::new(reader)),
+                #[cfg(feature = "zstd")]
+                Compression::Zstd => Box::new(zstd::Decoder::new(reader)?),
+            },
+        })
+    }
+    /// Takes a shared or exclusive advisory lock on the sibling `.lock` file if locking is enabled
+    fn lock_file(&self, shared: bool) -> Result<Option<StorageLock<'_>>> {
+        if !self.locking {
+            return Ok(None);
+        }
+        let path = with_suffix(&self.path, ".lock");
+        self.create_parent(&path).map_err(with_path(&path))?;
+        let file = OpenOptions::new()
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(&path)
+            .map_err(with_path(&path))?;
+        match self.lock_timeout {
+            Some(timeout) => lock_within(&file, shared, timeout),
+            None if shared => file.lock_shared(),
+            None => file.lock(),
+        }
+        .map_err(with_path(&path))?;
+        Ok(Some(StorageLock::new(FileLock(file))))
+    }
+    /// Opens the writer for replacing or appending to the file, compressing if the file is compressed
+    fn compressed_writer(&self, append: bool) -> Result<Box<dyn StorageWriter + '_>> {
+        let writer = self.writer(append)?;
+        Ok(match self.compression(&self.path) {
+            None => Box::new(writer),
+            Some(compression) => match compression {
+                #[cfg(feature = "gzip")]
+                Compression::Gzip => Box::new(Compressed::new(
+                    flate2::write::GzEncoder::new(writer, flate2::Compression::default()),
+                    flate2::write::GzEncoder::finish,
+                )),
+                #[cfg(feature = "zstd")]
+                Compression::Zstd => Box::new(Compressed::new(zstd::Encoder::new(writer, 0)?, zstd::Encoder::finish)),
+            },
+        })
+    }
+    /// Opens the writer for replacing or appending to the file
+    fn writer(&self, append: bool) -> Result<FileWriter<'_>> {
+        let crc = match self.sidecar {
+            Some(_) if append => Some(self.current_crc()?),
+            Some(_) => Some(Crc32::new()),
+            None => None,
+        };
+        let (file, temp) = if append {
+            self.rotate_backups()?;
+            (self.create(&self.path, OpenOptions::new().append(true))?, None)
+        } else if self.atomic_writes {
+            let temp = self.temp();
+            (self.create(temp.path(), OpenOptions::new().write(true).truncate(true))?, Some(temp))
+        } else {
+            self.rotate_backups()?;
+            (self.create(&self.path, OpenOptions::new().write(true).truncate(true))?, None)
+        };
+        Ok(FileWriter {
+            storage: self,
+            file,
+            temp,
+            crc,
+            preallocated: false,
+        })
+    }
+}
+
+/// Errors include the path of the file, which is displayed with them.
+impl Storage for FileStorage {
+    fn metadata(&self) -> Result<Option<StorageMetadata>> {
+        #[cfg(feature = "watch")]
+        if self.watch {
+            return self.watched_metadata().map_err(with_path(&self.path));
+        }
+        self.stat().map_err(with_path(&self.path))
+    }
+    /// Reads the newest of the file and the search paths, or the load path while the file doesn't exist
+    fn read(&self) -> Result<Box<dyn io::Read + '_>> {
+        let path = self.newest().map_err(with_path(&self.path))?.map_or(&*self.path, |(path, _)| path);
+        let reader: Box<dyn Read> = match (self.sidecar, self.load_timeout) {
+            (Some(version), _) => self.read_checked(path, version).map_err(with_path(path))?,
+            // Read in full on the helper thread, so that reading afterwards can't block
+            (None, Some(_)) => Box::new(Cursor::new(self.timed(path, |path| fs::read(path)).map_err(with_path(path))?)),
+            (None, None) => Box::new(File::open(path).map_err(with_path(path))?),
+        };
+        self.decompress(path, reader).map_err(with_path(path))
+    }
+    /// With atomic writes, this writes to a temporary file which is only renamed over the file
+    /// when committed, so that the file is never left partially written.
+    /// Backups are rotated right before the file is replaced.
+    fn write(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        self.compressed_writer(false).map_err(with_path(&self.path))
+    }
+    /// Appends to the file in place, creating it if it doesn't exist
+    ///
+    /// Backups are rotated before appending.
+    fn append(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        self.compressed_writer(true).map_err(with_path(&self.path))
+    }
+    fn delete(&self) -> Result<()> {
+        match fs::remove_file(&self.path) {
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+            r => r.map_err(with_path(&self.path))?,
+        }
+        if self.sidecar.is_some() {
+            let path = sidecar_path(&self.path);
+            match fs::remove_file(&path) {
+                Err(ref e) if e.kind() == io::ErrorKind::NotFound => (),
+                r => r.map_err(with_path(&path))?,
+            }
+        }
+        Ok(())
+    }
+    #[inline]
+    fn file_path(&self) -> Option<&Path> {
+        Some(&self.path)
+    }
+    #[inline]
+    /// Without atomic writes, the file is truncated and rewritten as it's written
+    fn writes_in_place(&self) -> bool {
+        !self.atomic_writes
+    }
+    /// Creates and removes a temporary file next to the file, creating the missing parent directories
+    /// first if that is enabled
+    fn check_writable(&self) -> Result<()> {
+        let temp = TempPath(Some(probe_path(&self.path)));
+        self.create(temp.path(), OpenOptions::new().write(true)).map(drop).map_err(with_path(temp.path()))
+    }
+    /// Takes an exclusive advisory lock (`flock` on Unix, `LockFileEx` on Windows) if locking is enabled
+    ///
+    /// The lock is taken on a sibling `.lock` file, since saving replaces the file itself.
+    fn lock(&self) -> Result<Option<StorageLock<'_>>> {
+        self.lock_file(false)
+    }
+    /// Takes a shared advisory lock on the same file as [`lock`](Storage::lock) if locking is enabled
+    fn lock_shared(&self) -> Result<Option<StorageLock<'_>>> {
+        self.lock_file(true)
+    }
+}
+
+struct FileWriter<'a> {
+    storage: &'a FileStorage,
+    file: File,
+    /// The temporary file being written to, with atomic writes
+    temp: Option<TempPath>,
+    /// The CRC-32 of the file as written so far, if it has a sidecar
+    crc: Option<Crc32>,
+    /// Whether space was reserved, so that the file has to be truncated to what was written
+    preallocated: bool,
+}
+
+impl FileWriter<'_> {
+    fn finish(self) -> Result<StorageMetadata> {
+        let FileWriter { storage, mut file, temp, crc, preallocated } = self;
+        file.flush()?;
+        if preallocated {
+            // In case less was written than reserved
+            let len = file.stream_position()?;
+            file.set_len(len)?;
+        }
+        if temp.is_some() {
+            storage.copy_permissions(&file)?;
+        }
+        if storage.durable {
+            file.sync_all()?;
+        }
+        let metadata = file.metadata()?;
+        // Renaming doesn't change the modification time, and open files can't be renamed on Windows
+        drop(file);
+
+        if let Some(temp) = temp {
+            storage.rotate_backups()?;
+            temp.persist(&storage.path)?;
+            storage.sync_dir()?;
+        }
+        // Written after the file, so until then a reader sees a mismatch and retries
+        if let (Some(version), Some(crc)) = (storage.sidecar, crc) {
+            storage.write_sidecar(Sidecar { version, len: metadata.len(), crc32: crc.finish(), saved: SystemTime::now() })?;
+        }
+        fs_metadata(&metadata)
+    }
+}
+
+impl Write for FileWriter<'_> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        let n = self.file.write(buf).map_err(with_path(&self.storage.path))?;
+        if let Some(crc) = &mut self.crc {
+            crc.write_all(&buf[..n])?;
+        }
+        Ok(n)
+    }
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        self.file.flush().map_err(with_path(&self.storage.path))
+    }
+}
+
+impl StorageWriter for FileWriter<'_> {
+    fn commit(self: Box<Self>) -> Result<StorageMetadata> {
+        let path = self.storage.path();
+        self.finish().map_err(with_path(path))
+    }
+    /// Syncs the written data to disk, even if saves aren't durable otherwise
+    ///
+    /// Without atomic writes, the file is already replaced then.
+    fn prepare(&mut self) -> Result<()> {
+        self.file.flush().and_then(|()| self.file.sync_all()).map_err(with_path(&self.storage.path))
+    }
+    /// Uses `fallocate` on Linux, falling back to extending the file if the filesystem doesn't support it
+    fn preallocate(&mut self, len: u64) -> Result<()> {
+        allocate(&self.file, len).map_err(with_path(&self.storage.path))?;
+        self.preallocated = true;
+        Ok(())
+    }
+}
+
+/// A compression format of files, chosen by their extension
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Compression {
+    #[cfg(feature = "gzip")]
+    /// `.gz`
+    Gzip,
+    #[cfg(feature = "zstd")]
+    /// `.zst`
+    Zstd,
+}
+
+impl Compression {
+    fn of(path: &Path) -> Option<Self> {
+        match path.extension()?.to_str()? {
+            #[cfg(feature = "gzip")]
+            "gz" => Some(Compression::Gzip),
+            #[cfg(feature = "zstd")]
+            "zst" => Some(Compression::Zstd),
+            _ => None,
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+/// The path that formats see for a file, without the extension of its compression format
+///
+/// For example, the format of `data.json.gz` is that of `data.json`.
+pub(crate) fn format_path(path: &Path) -> &Path {
+    match (Compression::of(path), path.file_stem()) {
+        (Some(_), Some(stem)) => Path::new(stem),
+        _ => path,
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+/// A file writer behind an encoder, which is finished before the file is committed
+struct Compressed<'a, E> {
+    encoder: Option<E>,
+    /// The file once the encoder has been finished by preparing
+    prepared: Option<FileWriter<'a>>,
+    finish: fn(E) -> Result<FileWriter<'a>>,
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+impl<'a, E: Write> Compressed<'a, E> {
+    fn new(encoder: E, finish: fn(E) -> Result<FileWriter<'a>>) -> Self {
+        Compressed { encoder: Some(encoder), prepared: None, finish }
+    }
+    fn into_file(self) -> Result<FileWriter<'a>> {
+        match (self.prepared, self.encoder) {
+            (Some(file), _) => Ok(file),
+            (None, Some(encoder)) => (self.finish)(encoder),
+            (None, None) => unreachable!("encoder is only taken when preparing"),
+        }
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+impl<E: Write> Write for Compressed<'_, E> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        match &mut self.encoder {
+            Some(encoder) => encoder.write(buf),
+            None => Err(io::Error::other("written to after being prepared")),
+        }
+    }
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        match &mut self.encoder {
+            Some(encoder) => encoder.flush(),
+            None => Ok(()),
+        }
+    }
+}
+
+#[cfg(any(feature = "gzip", feature = "zstd"))]
+impl<E: Write> StorageWriter for Compressed<'_, E> {
+    fn commit(self: Box<Self>) -> Result<StorageMetadata> {
+        Box::new(self.into_file()?).commit()
+    }
+    /// Finishes the encoder, so that all of the compressed data is synced to disk
+    fn prepare(&mut self) -> Result<()> {
+        if let Some(encoder) = self.encoder.take() {
+            self.prepared = Some((self.finish)(encoder)?);
+        }
+        self.prepared.as_mut().map_or(Ok(()), FileWriter::prepare)
+    }
+}
+
+/// Path of a temporary sibling file, which is removed when dropped unless persisted
+struct TempPath(Option<PathBuf>);
+
+impl TempPath {
+    fn path(&self) -> &Path {
+        self.0.as_deref().expect("temporary file has been persisted")
+    }
+    /// Renames the temporary file to the given path, replacing it if it exists
+    ///
+    /// On Windows, `fs::rename` uses `MoveFileExW` with `MOVEFILE_REPLACE_EXISTING`, which works
+    /// while the file is open for reading, as long as it was opened with `FILE_SHARE_DELETE`
+    /// like `File::open` does.
+    fn persist(mut self, to: &Path) -> Result<()> {
+        fs::rename(self.path(), to)?;
+        self.0 = None;
+        Ok(())
+    }
+}
+
+impl Drop for TempPath {
+    fn drop(&mut self) {
+        if let Some(path) = &self.0 {
+            let _ = fs::remove_file(path);
+        }
+    }
+}
+
+/// Reader that fails with the given error, once
+struct FailingReader(Option<io::Error>);
+
+impl Read for FailingReader {
+    fn read(&mut self, _buf: &mut [u8]) -> Result<usize> {
+        match self.0.take() {
+            Some(e) => Err(e),
+            None => Ok(0),
+        }
+    }
+}
+
+/// An advisory lock on a lock file, released when dropped
+struct FileLock(File);
+
+impl Drop for FileLock {
+    fn drop(&mut self) {
+        let _ = self.0.unlock();
+    }
+}
+
+/// Takes a shared or exclusive lock on the file, failing with `TimedOut` if it's held by another
+/// for longer than `timeout`
+fn lock_within(file: &File, shared: bool, timeout: Duration) -> Result<()> {
+    let start = Instant::now();
+    let mut backoff = LOCK_POLL_INTERVAL;
+    loop {
+        let locked = if shared { file.try_lock_shared() } else { file.try_lock() };
+        match locked {
+            Ok(()) => return Ok(()),
+            Err(TryLockError::WouldBlock) if start.elapsed() < timeout => {
+                thread::sleep(backoff.min(timeout.saturating_sub(start.elapsed())));
+                backoff = (backoff * 2).min(LOCK_POLL_MAX_INTERVAL);
+            }
+            Err(TryLockError::WouldBlock) => {
+                return Err(io::Error::new(io::ErrorKind::TimedOut, format!("still locked after {:?}", timeout)));
+            }
+            Err(TryLockError::Error(e)) => return Err(e),
+        }
+    }
+}
+
+/// Reserves space for the first `len` bytes of the file, extending it to that length
+fn allocate(file: &File, len: u64) -> Result<()> {
+    #[cfg(target_os = "linux")]
+    {
+        use std::{convert::TryFrom, os::unix::io::AsRawFd};
+        let len = libc::off_t::try_from(len).map_err(|e| io::Error::new(io::ErrorKind::InvalidInput, e))?;
+        // SAFETY: The descriptor stays open while `file` is borrowed
+        if unsafe { libc::fallocate(file.as_raw_fd(), 0, 0, len) } == 0 {
+            return Ok(());
+        }
+        let e = io::Error::last_os_error();
+        if e.raw_os_error() != Some(libc::EOPNOTSUPP) {
+            return Err(e);
+        }
+    }
+    file.set_len(len)
+}
+
+/// Gets the metadata of the file, or `None` if it doesn't exist
+fn stat(path: &Path) -> Result<Option<StorageMetadata>> {
+    match fs::metadata(path) {
+        Ok(m) => fs_metadata(&m).map(Some),
+        Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+        Err(e) => Err(e),
+    }
+}
+
+/// Whether the contents of the two files differ, comparing them byte by byte
+///
+/// The files are streamed, stopping at the first difference, and files of different lengths
+/// aren't read at all. A missing file differs from an existing one, even if it's empty.
+pub fn files_differ<A: AsRef<Path>, B: AsRef<Path>>(a: A, b: B) -> Result<bool> {
+    let (a, b) = (a.as_ref(), b.as_ref());
+    match (stat(a)?, stat(b)?) {
+        (None, None) => return Ok(false),
+        (Some(ma), Some(mb)) if ma.len == mb.len => (),
+        _ => return Ok(true),
+    }
+    let mut a = BufReader::new(File::open(a)?);
+    let mut b = BufReader::new(File::open(b)?);
+    loop {
+        let (chunk_a, chunk_b) = (a.fill_buf()?, b.fill_buf()?);
+        let len = chunk_a.len().min(chunk_b.len());
+        if len == 0 {
+            return Ok(chunk_a.len() != chunk_b.len());
+        }
+        if chunk_a[..len] != chunk_b[..len] {
+            return Ok(true);
+        }
+        a.consume(len);
+        b.consume(len);
+    }
+}
+
+/// Converts the metadata of a file, including its device and inode numbers on Unix
+pub(crate) fn fs_metadata(m: &fs::Metadata) -> Result<StorageMetadata> {
+    let metadata = StorageMetadata::new(m.modified()?, m.len());
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::MetadataExt;
+        Ok(metadata.with_file_id((m.dev(), m.ino())))
+    }
+    #[cfg(not(unix))]
+    Ok(metadata)
+}
+
+/// Path of the sidecar metadata file of the file at the given path
+fn sidecar_path(path: &Path) -> PathBuf {
+    with_suffix(path, ".meta")
+}
+
+/// Path of the sibling file that saves are written to before being renamed into place
+///
+/// Each call gives a new path, so that concurrent saves in the same process don't write to the same file.
+pub(crate) fn temp_path(path: &Path) -> PathBuf {
+    static TEMPS: AtomicUsize = AtomicUsize::new(0);
+    with_suffix(path, &format!(".tmp-{}-{}", process::id(), TEMPS.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Path of a sibling file for checking that the directory is writable, which is never used for saves
+///
+/// Each call gives a new path, so that checks don't remove each other's files.
+fn probe_path(path: &Path) -> PathBuf {
+    static PROBES: AtomicUsize = AtomicUsize::new(0);
+    with_suffix(path, &format!(".probe-{}-{}", process::id(), PROBES.fetch_add(1, Ordering::Relaxed)))
+}
+
+/// Path with the given suffix appended to the file name
+fn with_suffix(path: &Path, suffix: &str) -> PathBuf {
+    let mut s = OsString::from(path.as_os_str());
+    s.push(suffix);
+    s.into()
+}
@@ -0,0 +1,140 @@
+use std::{
+    io::{self, Write, Result},
+    sync::{Arc, Mutex, MutexGuard},
+    time::{Duration, SystemTime},
+};
+
+use super::{Storage, StorageMetadata, StorageWriter};
+
+/// How far the synthetic clock advances on each write
+const WRITE_TICK: Duration = Duration::from_secs(1);
+
+#[derive(Debug)]
+struct State {
+    data: Option<Vec<u8>>,
+    /// The synthetic clock
+    now: SystemTime,
+    modified: SystemTime,
+}
+
+/// Stores the data in memory, with a synthetic modification time
+///
+/// This is meant for testing [`Cache`](crate::Cache) implementations and reloading behaviour
+/// without touching the filesystem.
+/// Each write advances the synthetic clock by a second and stamps the data with it,
+/// and the clock can also be advanced manually with [`advance`](MemoryStorage::advance).
+///
+/// Clones share the same data, so a clone can be used to simulate modifications made by others.
+#[derive(Debug, Clone)]
+pub struct MemoryStorage {
+    state: Arc<Mutex<State>>,
+}
+
+impl Default for MemoryStorage {
+    fn default() -> Self {
+        MemoryStorage::new()
+    }
+}
+
+impl MemoryStorage {
+    /// Makes an empty storage, with the clock at `UNIX_EPOCH`
+    pub fn new() -> Self {
+        MemoryStorage {
+            state: Arc::new(Mutex::new(State {
+                data: None,
+                now: SystemTime::UNIX_EPOCH,
+                modified: SystemTime::UNIX_EPOCH,
+            })),
+        }
+    }
+    /// Makes a storage already holding the given data, as if it had been written
+    pub fn with_data<D: Into<Vec<u8>>>(data: D) -> Self {
+        let storage = MemoryStorage::new();
+        storage.set_data(data);
+        storage
+    }
+    fn state(&self) -> MutexGuard<'_, State> {
+        self.state.lock().unwrap_or_else(|e| e.into_inner())
+    }
+    /// A copy of the stored data, or `None` if nothing is stored
+    pub fn data(&self) -> Option<Vec<u8>> {
+        self.state().data.clone()
+    }
+    /// Replaces the stored data as if it had been written
+    pub fn set_data<D: Into<Vec<u8>>>(&self, data: D) {
+        let mut state = self.state();
+        state.data = Some(data.into());
+        state.now += WRITE_TICK;
+        state.modified = state.now;
+    }
+    /// Removes the stored data
+    pub fn remove(&self) {
+        self.state().data = None;
+    }
+    /// The current time of the synthetic clock
+    pub fn now(&self) -> SystemTime {
+        self.state().now
+    }
+    /// Advances the synthetic clock without modifying the data
+    pub fn advance(&self, duration: Duration) {
+        self.state().now += duration;
+    }
+    /// Stamps the data with the current time of the synthetic clock without changing it
+    pub fn touch(&self) {
+        let mut state = self.state();
+        state.modified = state.now;
+    }
+}
+
+impl Storage for MemoryStorage {
+    fn metadata(&self) -> Result<Option<StorageMetadata>> {
+        let state = self.state();
+        Ok(state.data.as_ref().map(|data| StorageMetadata::new(state.modified, data.len() as u64)))
+    }
+    fn read(&self) -> Result<Box<dyn io::Read + '_>> {
+        match self.data() {
+            Some(data) => Ok(Box::new(io::Cursor::new(data))),
+            None => Err(io::Error::new(io::ErrorKind::NotFound, "nothing is stored")),
+        }
+    }
+    fn write(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        Ok(Box::new(MemoryWriter {
+            storage: self,
+            buf: Vec::new(),
+        }))
+    }
+    fn append(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        Ok(Box::new(MemoryWriter {
+            storage: self,
+            buf: self.data().unwrap_or_default(),
+        }))
+    }
+    fn delete(&self) -> Result<()> {
+        self.remove();
+        Ok(())
+    }
+}
+
+struct MemoryWriter<'a> {
+    storage: &'a MemoryStorage,
+    buf: Vec<u8>,
+}
+
+impl Write for MemoryWriter<'_> {
+    #[inline]
+    fn write(&mut self, buf: &[u8]) -> Result<usize> {
+        self.buf.write(buf)
+    }
+    #[inline]
+    fn flush(&mut self) -> Result<()> {
+        Ok(())
+    }
+}
+
+impl StorageWriter for MemoryWriter<'_> {
+    fn commit(self: Box<Self>) -> Result<StorageMetadata> {
+        let len = self.buf.len() as u64;
+        self.storage.set_data(self.buf);
+        Ok(StorageMetadata::new(self.storage.state().modified, len))
+    }
+}
@@ -0,0 +1,161 @@
+//! Where the data of a cache is stored
+//!
+//! [`Cached`](crate::Cached) uses a [`FileStorage`] by default,
+//! but can be backed by anything implementing [`Storage`].
+
+use std::{
+    io::{self, Read, Write, Result},
+    path::Path,
+    time::SystemTime,
+};
+
+#[cfg(feature = "sha2")]
+mod content;
+mod file;
+mod memory;
+mod sidecar;
+#[cfg(feature = "watch")]
+mod watch;
+
+#[cfg(feature = "sha2")]
+pub use self::content::ContentAddressed;
+pub use self::file::{FileStorage, files_differ};
+pub use self::memory::MemoryStorage;
+pub use self::sidecar::Sidecar;
+#[cfg(any(feature = "tokio", feature = "sha2"))]
+pub(crate) use self::file::fs_metadata;
+#[cfg(feature = "tokio")]
+pub(crate) use self::file::temp_path;
+#[cfg(feature = "serde")]
+pub(crate) use self::file::format_path;
+
+/// A place to store the serialized data of a cache
+pub trait Storage {
+    /// Gets the metadata of the stored data, or `None` if nothing is stored yet
+    ///
+    /// This is used to decide whether the data has changed since it was last read or written,
+    /// so it should be cheap compared to reading the data.
+    fn metadata(&self) -> Result<Option<StorageMetadata>>;
+    /// Opens the stored data for reading
+    fn read(&self) -> Result<Box<dyn Read + '_>>;
+    /// Opens a writer for replacing the stored data
+    ///
+    /// The stored data should only be replaced once [`StorageWriter::commit`] is called,
+    /// and dropping the writer without committing should discard what was written, if possible.
+    fn write(&self) -> Result<Box<dyn StorageWriter + '_>>;
+    /// Opens a writer for appending to the stored data
+    ///
+    /// This only needs to be implemented for using [`CachedBuilder::append`](crate::CachedBuilder::append),
+    /// the default returns an `Unsupported` error.
+    fn append(&self) -> Result<Box<dyn StorageWriter + '_>> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "appending is not supported by this storage"))
+    }
+    /// Deletes the stored data, doing nothing if nothing is stored
+    ///
+    /// This only needs to be implemented for using [`Cached::clear`](crate::Cached::clear),
+    /// the default returns an `Unsupported` error.
+    fn delete(&self) -> Result<()> {
+        Err(io::Error::new(io::ErrorKind::Unsupported, "deleting is not supported by this storage"))
+    }
+    /// The path of the file holding the data, if it's stored in a file
+    ///
+    /// This is passed to [`Cache::load_from`](crate::Cache::load_from). Defaults to `None`.
+    fn file_path(&self) -> Option<&Path> {
+        None
+    }
+    /// Takes a lock on the stored data for the duration of an operation,
+    /// which is released when the returned guard is dropped
+    ///
+    /// Storages that don't need locking return `None`, which is the default.
+    fn lock(&self) -> Result<Option<StorageLock<'_>>> {
+        Ok(None)
+    }
+    /// Takes a lock for an operation that only reads the stored data, which only excludes
+    /// [`lock`](Storage::lock), so that readers don't block each other
+    ///
+    /// The default takes the exclusive lock with `lock`.
+    fn lock_shared(&self) -> Result<Option<StorageLock<'_>>> {
+        self.lock()
+    }
+    /// Whether [`write`](Storage::write) changes the stored data as it's written instead of on commit,
+    /// which makes [`commit_all`](crate::commit_all) refuse the storage
+    ///
+    /// The default says it doesn't.
+    fn writes_in_place(&self) -> bool {
+        false
+    }
+    /// Checks that data can be written, without changing the stored data
+    ///
+    /// This is used by [`CachedBuilder::fallback_in_memory`](crate::CachedBuilder::fallback_in_memory).
+    /// The default assumes that it can.
+    fn check_writable(&self) -> Result<()> {
+        Ok(())
+    }
+}
+
+/// Writer returned by [`Storage::write`]
+pub trait StorageWriter: Write {
+    /// Makes what was written the stored data, returning its new metadata
+    fn commit(self: Box<Self>) -> Result<StorageMetadata>;
+    /// Makes what was written durable without committing it yet, so that committing can't fail
+    /// for lack of space, for committing several writes together with [`commit_all`](crate::commit_all)
+    ///
+    /// The default flushes the writer.
+    fn prepare(&mut self) -> Result<()> {
+        self.flush()
+    }
+    /// Reserves space for data of the given length before it's written,
+    /// see [`CachedBuilder::preallocate`](crate::CachedBuilder::preallocate)
+    ///
+    /// The default does nothing.
+    fn preallocate(&mut self, len: u64) -> Result<()> {
+        let _ = len;
+        Ok(())
+    }
+}
+
+/// The metadata of stored data used to tell whether it has changed
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[non_exhaustive]
+pub struct StorageMetadata {
+    /// When the data was last modified
+    pub modified: SystemTime,
+    /// The length of the data in bytes
+    pub len: u64,
+    /// Identifies the file holding the data, such as the device and inode numbers on Unix
+    ///
+    /// This tells when a file was replaced by another, even if the modification time and length didn't change.
+    pub file_id: Option<(u64, u64)>,
+}
+
+impl StorageMetadata {
+    #[inline]
+    /// Makes metadata from the modification time and length
+    pub fn new(modified: SystemTime, len: u64) -> Self {
+        StorageMetadata { modified, len, file_id: None }
+    }
+    #[inline]
+    /// Sets what identifies the file holding the data, see [`file_id`](StorageMetadata::file_id)
+    pub fn with_file_id(self, file_id: (u64, u64)) -> Self {
+        StorageMetadata { file_id: Some(file_id), ..self }
+    }
+}
+
+/// Anything can be held as a lock guard
+trait Guard {}
+impl<T> Guard for T {}
+
+/// Guard for a lock taken by [`Storage::lock`], releasing the lock when dropped
+pub struct StorageLock<'a> {
+    _guard: Box<dyn Guard + 'a>,
+}
+
+impl<'a> StorageLock<'a> {
+    #[inline]
+    /// Wraps a value that releases the lock when dropped
+    pub fn new<G: 'a>(guard: G) -> Self {
+        StorageLock {
+            _guard: Box::new(guard),
+        }
+    }
+}
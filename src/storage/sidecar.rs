@@ -0,0 +1,73 @@
+use std::{
+    io::{self, Error, ErrorKind, Result},
+    fs,
+    path::Path,
+    time::{Duration, SystemTime},
+};
+
+/// What the `<path>.meta` file next to a cache file records about it,
+/// see [`CachedBuilder::sidecar`](crate::CachedBuilder::sidecar)
+///
+/// The file is a JSON object with the fields below, with the save time in milliseconds
+/// since the Unix epoch, e.g. `{"version":2,"len":11,"crc32":222957957,"saved":1700000000123}`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Sidecar {
+    /// The schema version the data was saved with
+    pub version: u32,
+    /// The length of the data in bytes
+    pub len: u64,
+    /// The CRC-32 (IEEE) of the data
+    pub crc32: u32,
+    /// When the data was saved
+    pub saved: SystemTime,
+}
+
+impl Sidecar {
+    /// Reads the sidecar at the given path, or gives `None` if it doesn't exist
+    pub(crate) fn read(path: &Path) -> Result<Option<Self>> {
+        match fs::read_to_string(path) {
+            Ok(s) => Self::parse(&s).map(Some),
+            Err(ref e) if e.kind() == ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(e),
+        }
+    }
+    fn parse(s: &str) -> Result<Self> {
+        let invalid = || Error::new(ErrorKind::InvalidData, "sidecar isn't a valid metadata object");
+        let fields = s.trim().strip_prefix('{').and_then(|s| s.strip_suffix('}')).ok_or_else(invalid)?;
+        let (mut version, mut len, mut crc32, mut saved) = (None, None, None, None);
+        for field in fields.split(',') {
+            let (key, value) = field.split_once(':').ok_or_else(invalid)?;
+            let value = value.trim();
+            match key.trim() {
+                "\"version\"" => version = value.parse().ok(),
+                "\"len\"" => len = value.parse().ok(),
+                "\"crc32\"" => crc32 = value.parse().ok(),
+                "\"saved\"" => saved = value.parse().ok().map(|ms| SystemTime::UNIX_EPOCH + Duration::from_millis(ms)),
+                // Unknown fields are left for newer versions
+                _ => (),
+            }
+        }
+        match (version, len, crc32, saved) {
+            (Some(version), Some(len), Some(crc32), Some(saved)) => Ok(Sidecar { version, len, crc32, saved }),
+            _ => Err(invalid()),
+        }
+    }
+    /// The contents of the sidecar file
+    pub(crate) fn to_json(self) -> String {
+        let saved = self.saved.duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis();
+        format!(r#"{{"version":{},"len":{},"crc32":{},"saved":{}}}"#, self.version, self.len, self.crc32, saved)
+    }
+    /// Checks that data of the given length and CRC-32 is what was saved with a schema version
+    /// no newer than `version`
+    pub(crate) fn verify(&self, version: u32, len: u64, crc32: u32) -> io::Result<()> {
+        if self.version > version {
+            Err(Error::new(ErrorKind::InvalidData, format!(
+                "saved with schema version {}, which is newer than {}", self.version, version,
+            )))
+        } else if self.len != len || self.crc32 != crc32 {
+            Err(Error::new(ErrorKind::InvalidData, "data doesn't match the checksum in its sidecar"))
+        } else {
+            Ok(())
+        }
+    }
+}
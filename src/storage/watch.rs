@@ -0,0 +1,80 @@
+use std::{
+    io,
+    ffi::OsString,
+    fmt::{self, Debug},
+    path::Path,
+    sync::{
+        Arc, Mutex,
+        atomic::{AtomicBool, Ordering},
+    },
+};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher as _};
+
+use super::StorageMetadata;
+
+/// Watches the directory of a cache file, recording when the file might have changed
+///
+/// Watching stops when this is dropped.
+pub(crate) struct Watcher {
+    _watcher: RecommendedWatcher,
+    changed: Arc<AtomicBool>,
+    /// The metadata from when the file was last checked
+    last: Mutex<Option<Option<StorageMetadata>>>,
+}
+
+impl Watcher {
+    pub fn new(path: &Path) -> io::Result<Self> {
+        // Set initially, so that the file is checked the first time
+        let changed = Arc::new(AtomicBool::new(true));
+        let file_name: Option<OsString> = path.file_name().map(Into::into);
+
+        let flag = changed.clone();
+        let mut watcher = notify::recommended_watcher(move |res: notify::Result<Event>| {
+            let relevant = match res {
+                Ok(event) => event.paths.iter().any(|p| p.file_name() == file_name.as_deref()),
+                // Events might have been missed
+                Err(_) => true,
+            };
+            if relevant {
+                flag.store(true, Ordering::Release);
+            }
+        }).map_err(io::Error::other)?;
+
+        let dir = match path.parent() {
+            Some(dir) if !dir.as_os_str().is_empty() => dir,
+            _ => Path::new("."),
+        };
+        watcher.watch(dir, RecursiveMode::NonRecursive).map_err(io::Error::other)?;
+
+        Ok(Watcher {
+            _watcher: watcher,
+            changed,
+            last: Mutex::new(None),
+        })
+    }
+    /// Gets the metadata of the file using `stat`,
+    /// unless the file hasn't changed since then, in which case the last metadata is given
+    pub fn metadata<F>(&self, stat: F) -> io::Result<Option<StorageMetadata>>
+    where F: FnOnce() -> io::Result<Option<StorageMetadata>> {
+        let mut last = self.last.lock().unwrap_or_else(|e| e.into_inner());
+        // Taken before checking, so changes made while checking aren't missed
+        let changed = self.changed.swap(false, Ordering::AcqRel);
+        match *last {
+            Some(metadata) if !changed => Ok(metadata),
+            _ => {
+                let metadata = stat().inspect_err(|_| self.changed.store(true, Ordering::Release))?;
+                *last = Some(metadata);
+                Ok(metadata)
+            }
+        }
+    }
+}
+
+impl Debug for Watcher {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.debug_struct("Watcher")
+            .field("changed", &self.changed.load(Ordering::Relaxed))
+            .finish()
+    }
+}
@@ -0,0 +1,287 @@
+//! A thread-safe variant of [`Cached`](crate::Cached)
+
+use std::{
+    result::Result as StdResult,
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, RwLockReadGuard, RwLockWriteGuard, TryLockError, mpsc::{self, RecvTimeoutError, Sender}},
+    thread::{self, JoinHandle},
+    time::{Duration, Instant, SystemTime},
+};
+
+use crate::{
+    Cache, CacheError, CacheStats, ContentionStats, MutError, Result, persist,
+    stats::{AtomicContention, AtomicStats},
+    storage::{FileStorage, StorageMetadata},
+};
+
+/// What is known about the cache file since it was last loaded or saved, also used by the `tokio` module
+#[derive(Debug)]
+pub(crate) struct FileState {
+    last_modified: SystemTime,
+    last_len: u64,
+    last_file_id: Option<(u64, u64)>,
+    pub(crate) content_hash: Option<u64>,
+}
+
+impl FileState {
+    /// The state before anything has been loaded or saved
+    pub(crate) fn new() -> Self {
+        FileState {
+            last_modified: SystemTime::UNIX_EPOCH,
+            last_len: 0,
+            last_file_id: None,
+            content_hash: None,
+        }
+    }
+    /// Whether the file with the given metadata has changed since it was last loaded or saved
+    pub(crate) fn has_changed(&self, metadata: &StorageMetadata) -> bool {
+        persist::has_changed(metadata, self.last_modified, self.last_len, self.last_file_id)
+    }
+    pub(crate) fn synced(&mut self, metadata: &StorageMetadata, hash: u64) {
+        self.last_modified = metadata.modified;
+        self.last_len = metadata.len;
+        self.last_file_id = metadata.file_id;
+        self.content_hash = Some(hash);
+    }
+}
+
+fn poisoned<E>(_: E) -> CacheError {
+    CacheError::Poisoned
+}
+
+#[derive(Debug)]
+/// Like [`Cached`](crate::Cached), but using locks instead of `RefCell`s,
+/// so that it can be shared between threads
+///
+/// The cache file is checked and loaded in exactly the same way.
+pub struct SyncCached<T: Cache> {
+    state: RwLock<FileState>,
+    storage: FileStorage,
+    stats: AtomicStats,
+    contention: AtomicContention,
+    inner: RwLock<T>,
+}
+
+impl<T: Cache + Default> SyncCached<T> {
+    #[inline]
+    /// Make a new instance using the type's default function
+    pub fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        Self::new_with(T::default, path)
+    }
+}
+
+impl<T: Cache> SyncCached<T> {
+    /// Make a new instance using a custom default function
+    pub fn new_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> Result<Self> {
+        let ret = Self {
+            state: RwLock::new(FileState::new()),
+            storage: FileStorage::new(path),
+            stats: AtomicStats::default(),
+            contention: AtomicContention::default(),
+            inner: RwLock::new(default()),
+        };
+        ret.check_load().map(|()| ret)
+    }
+    #[inline]
+    /// The path of the cache file
+    pub fn path(&self) -> &Path {
+        self.storage.path()
+    }
+    #[inline]
+    /// Counters of the loads and saves done so far
+    pub fn stats(&self) -> CacheStats {
+        self.stats.snapshot()
+    }
+    #[inline]
+    /// How often threads had to wait for each other to access the instance, and for how long
+    ///
+    /// Locks are first tried without blocking, so only contended accesses are timed.
+    /// Lots of waiting suggests sharding the data over several instances or saving less often.
+    pub fn contention_stats(&self) -> ContentionStats {
+        self.contention.snapshot()
+    }
+    /// Takes the lock for reading, counting it if it has to wait
+    fn read_lock<'a, U>(&self, lock: &'a RwLock<U>) -> Result<RwLockReadGuard<'a, U>> {
+        match lock.try_read() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(_)) => Err(CacheError::Poisoned),
+            Err(TryLockError::WouldBlock) => {
+                let start = Instant::now();
+                let guard = lock.read().map_err(poisoned);
+                self.contention.blocked(false, start.elapsed());
+                guard
+            }
+        }
+    }
+    /// Takes the lock for writing, counting it if it has to wait
+    fn write_lock<'a, U>(&self, lock: &'a RwLock<U>) -> Result<RwLockWriteGuard<'a, U>> {
+        match lock.try_write() {
+            Ok(guard) => Ok(guard),
+            Err(TryLockError::Poisoned(_)) => Err(CacheError::Poisoned),
+            Err(TryLockError::WouldBlock) => {
+                let start = Instant::now();
+                let guard = lock.write().map_err(poisoned);
+                self.contention.blocked(true, start.elapsed());
+                guard
+            }
+        }
+    }
+    #[inline]
+    /// Loads the cache file if it has changed since it was last loaded or saved,
+    /// so that later accesses don't have to
+    ///
+    /// Since instances can be shared between threads, many can be warmed in parallel at startup,
+    /// e.g. by calling this on each of them in a thread spawned with `std::thread::scope`.
+    pub fn warm(&self) -> Result<()> {
+        self.check_load()
+    }
+    fn check_load(&self) -> Result<()> {
+        let metadata = match persist::stat(&self.storage)? {
+            Some(m) => m,
+            None => return Ok(()),
+        };
+        {
+            let state = self.read_lock(&self.state)?;
+            if !state.has_changed(&metadata) {
+                self.stats.skipped_reload();
+                return Ok(());
+            }
+        }
+
+        let mut state = self.write_lock(&self.state)?;
+        // Another thread might have loaded it while we were waiting for the lock
+        if state.has_changed(&metadata) {
+            self.load(&mut state, &metadata)?;
+        }
+        Ok(())
+    }
+    fn load(&self, state: &mut FileState, metadata: &StorageMetadata) -> Result<()> {
+        let read = persist::read(&self.storage, None, None, None);
+        self.stats.load(&read);
+        let (value, hash): (T, _) = read?;
+        let rewrite = value.needs_rewrite();
+
+        let mut inner = self.write_lock(&self.inner)?;
+        *inner = value;
+        state.synced(metadata, hash);
+        if rewrite {
+            self.save(state, &inner)?;
+        }
+        Ok(())
+    }
+    /// Re-reads the cache file even if it doesn't seem to have been modified,
+    /// discarding any unsaved changes to the inner value
+    pub fn reload(&self) -> Result<()> {
+        let mut state = self.write_lock(&self.state)?;
+        self.load(&mut state, &persist::existing_stat(&self.storage)?)
+    }
+    fn save(&self, state: &mut FileState, value: &T) -> Result<()> {
+        let written = persist::write(&self.storage, value, None, None, None);
+        self.stats.save(&written);
+        let (metadata, hash) = written?;
+        state.synced(&metadata, hash);
+        Ok(())
+    }
+    /// Writes the current in-memory value to the cache file,
+    /// regardless of whether it has been changed
+    pub fn save_now(&self) -> Result<()> {
+        let mut state = self.write_lock(&self.state)?;
+        let inner = self.read_lock(&self.inner)?;
+        self.save(&mut state, &inner)
+    }
+    /// Saves the inner value only if its serialized form differs from
+    /// what was last loaded from or saved to the cache file
+    ///
+    /// Returns whether the file was written.
+    pub fn save_if_dirty(&self) -> Result<bool> {
+        let mut state = self.write_lock(&self.state)?;
+        let inner = self.read_lock(&self.inner)?;
+        self.write_if_dirty(&mut state, &inner)
+    }
+    /// Saves the value if it differs from what was last loaded or saved, assuming the state is locked
+    fn write_if_dirty(&self, state: &mut FileState, value: &T) -> Result<bool> {
+        if state.content_hash == Some(persist::hash(&self.storage, value, None)?) {
+            Ok(false)
+        } else {
+            self.save(state, value).map(|()| true)
+        }
+    }
+    /// Get a read guard to the inner type
+    ///
+    /// The cache file is loaded while holding the lock on the inner value for writing, so calling `get`
+    /// or [`with_mut`](SyncCached::with_mut) again while the guard is held on the same thread can deadlock.
+    pub fn get(&self) -> Result<RwLockReadGuard<'_, T>> {
+        self.check_load()?;
+        self.read_lock(&self.inner)
+    }
+    /// Applies the given closure to a mutable reference to the inner value
+    /// and automatically saves the state to the cache file afterwards,
+    /// unless the closure left the serialized value unchanged
+    ///
+    /// If the save fails after the closure was run, the closure's result is still
+    /// available from the error, see [`MutError::Unsaved`].
+    /// Other threads can't load the cache file between the closure and the save, so its changes can't be lost,
+    /// and this must not be called while holding a guard from [`get`](SyncCached::get) on the same thread.
+    pub fn with_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> StdResult<R, MutError<R>> {
+        self.check_load().map_err(MutError::NotRun)?;
+        let mut state = self.write_lock(&self.state).map_err(MutError::NotRun)?;
+        let mut inner = self.write_lock(&self.inner).map_err(MutError::NotRun)?;
+        let r = f(&mut inner);
+        match self.write_if_dirty(&mut state, &inner) {
+            Ok(_) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Consumes the instance, and returns the inner `T`.
+    pub fn into_inner(self) -> Result<T> {
+        self.inner.into_inner().map_err(poisoned)
+    }
+}
+
+impl<T: Cache + Send + Sync + 'static> SyncCached<T> {
+    /// Spawns a thread calling [`save_if_dirty`](SyncCached::save_if_dirty) every `interval`,
+    /// so that changes are saved even if nothing else saves them
+    ///
+    /// The thread runs until the returned guard is dropped or stopped, saving one last time then.
+    /// Since errors can't be returned from the thread, failed saves are only logged,
+    /// and are retried on the next interval.
+    pub fn spawn_autosave(self: Arc<Self>, interval: Duration) -> Autosave {
+        let (stop, stopped) = mpsc::channel::<()>();
+        let thread = thread::spawn(move || loop {
+            let last = !matches!(stopped.recv_timeout(interval), Err(RecvTimeoutError::Timeout));
+            if let Err(_e) = self.save_if_dirty() {
+                debug!("autosave of {} failed: {}", self.path().display(), _e);
+            }
+            if last {
+                break;
+            }
+        });
+        Autosave { stop: Some(stop), thread: Some(thread) }
+    }
+}
+
+/// Guard of the thread spawned by [`SyncCached::spawn_autosave`], which stops it when dropped
+#[derive(Debug)]
+#[must_use = "the autosave thread stops right away when the guard is dropped"]
+pub struct Autosave {
+    stop: Option<Sender<()>>,
+    thread: Option<JoinHandle<()>>,
+}
+
+impl Autosave {
+    #[inline]
+    /// Stops the thread after its last save, waiting for it to finish
+    pub fn stop(self) {
+        drop(self)
+    }
+}
+
+impl Drop for Autosave {
+    fn drop(&mut self) {
+        // Disconnecting the channel wakes the thread up
+        drop(self.stop.take());
+        if let Some(thread) = self.thread.take() {
+            let _ = thread.join();
+        }
+    }
+}
@@ -0,0 +1,182 @@
+//! An asynchronous variant of [`Cached`](crate::Cached) using tokio
+//!
+//! File operations are done with `tokio::fs`, so they don't block the executor.
+
+use std::{
+    io,
+    future::Future,
+    result::Result as StdResult,
+    path::{Path, PathBuf},
+};
+
+use ::tokio::{
+    fs,
+    io::{AsyncRead, AsyncWrite, AsyncWriteExt},
+    sync::{Mutex, MutexGuard, RwLock, RwLockReadGuard},
+};
+
+use crate::{
+    CacheError, MutError, Result,
+    hashing::hash,
+    storage::{StorageMetadata, fs_metadata, temp_path},
+    sync::FileState,
+};
+
+/// Asynchronous counterpart of [`Cache`](crate::Cache)
+///
+/// The methods can be implemented with `async fn`.
+pub trait AsyncCache: Sized + Send + Sync {
+    /// Write data such that if reading the data would yield the same structure
+    fn save<W: AsyncWrite + Unpin + Send>(&self, writer: W) -> impl Future<Output = io::Result<()>> + Send;
+    /// Load data that corresponds to the way it's saved
+    fn load<R: AsyncRead + Unpin + Send>(reader: R) -> impl Future<Output = io::Result<Self>> + Send;
+}
+
+#[derive(Debug)]
+/// Like [`SyncCached`](crate::sync::SyncCached), but with asynchronous methods
+///
+/// The cache file is checked and loaded in exactly the same way as for [`Cached`](crate::Cached).
+/// Saves are atomic, going through a temporary file that is synced to disk and renamed into place.
+/// The cache file isn't locked, so saves from other processes can be overwritten.
+pub struct AsyncCached<T: AsyncCache> {
+    state: Mutex<FileState>,
+    path: Box<Path>,
+    inner: RwLock<T>,
+}
+
+impl<T: AsyncCache + Default> AsyncCached<T> {
+    #[inline]
+    /// Make a new instance using the type's default function
+    pub async fn new<P: Into<PathBuf>>(path: P) -> Result<Self> {
+        Self::new_with(T::default, path).await
+    }
+}
+
+impl<T: AsyncCache> AsyncCached<T> {
+    /// Make a new instance using a custom default function
+    pub async fn new_with<F: FnOnce() -> T, P: Into<PathBuf>>(default: F, path: P) -> Result<Self> {
+        let ret = Self {
+            state: Mutex::new(FileState::new()),
+            path: path.into().into_boxed_path(),
+            inner: RwLock::new(default()),
+        };
+        drop(ret.check_load().await?);
+        Ok(ret)
+    }
+    #[inline]
+    /// The path of the cache file
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+    async fn stat(&self) -> Result<Option<StorageMetadata>> {
+        match fs::metadata(&self.path).await {
+            Ok(m) => fs_metadata(&m).map(Some).map_err(CacheError::Metadata),
+            Err(ref e) if e.kind() == io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(CacheError::Metadata(e)),
+        }
+    }
+    async fn check_load(&self) -> Result<MutexGuard<'_, FileState>> {
+        let mut state = self.state.lock().await;
+        if let Some(metadata) = self.stat().await? {
+            if state.has_changed(&metadata) {
+                self.load(&mut state, &metadata).await?;
+            }
+        }
+        Ok(state)
+    }
+    async fn load(&self, state: &mut FileState, metadata: &StorageMetadata) -> Result<()> {
+        let bytes = fs::read(&self.path).await.map_err(CacheError::Open)?;
+        let value = T::load(&bytes[..]).await.map_err(CacheError::Load)?;
+
+        *self.inner.write().await = value;
+        state.synced(metadata, hash(&bytes));
+        Ok(())
+    }
+    /// Re-reads the cache file even if it doesn't seem to have been modified,
+    /// discarding any unsaved changes to the inner value
+    pub async fn reload(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let metadata = self.stat().await?.ok_or_else(|| {
+            CacheError::Metadata(io::Error::new(io::ErrorKind::NotFound, "cache file doesn't exist"))
+        })?;
+        self.load(&mut state, &metadata).await
+    }
+    async fn serialize(value: &T) -> Result<Vec<u8>> {
+        let mut bytes = Vec::new();
+        match value.save(&mut bytes).await {
+            Ok(()) => Ok(bytes),
+            Err(source) => Err(CacheError::Write { source, bytes_written: bytes.len() as u64 }),
+        }
+    }
+    async fn write(&self, state: &mut FileState, bytes: &[u8]) -> Result<()> {
+        let temp_path = temp_path(&self.path);
+        let written = match Self::write_synced(&temp_path, bytes).await {
+            Ok(()) => fs::rename(&temp_path, &self.path).await,
+            Err(e) => Err(e),
+        };
+        if let Err(e) = written {
+            let _ = fs::remove_file(&temp_path).await;
+            return Err(CacheError::Save(e));
+        }
+
+        let metadata = self.stat().await?.ok_or_else(|| {
+            CacheError::Metadata(io::Error::new(io::ErrorKind::NotFound, "cache file disappeared after saving"))
+        })?;
+        state.synced(&metadata, hash(bytes));
+        Ok(())
+    }
+    /// Writes the bytes to a new file at the path and syncs it to disk
+    async fn write_synced(path: &Path, bytes: &[u8]) -> io::Result<()> {
+        let mut file = fs::File::create(path).await?;
+        file.write_all(bytes).await?;
+        file.sync_all().await
+    }
+    async fn write_if_dirty(&self, state: &mut FileState, value: &T) -> Result<bool> {
+        let bytes = Self::serialize(value).await?;
+        if state.content_hash == Some(hash(&bytes)) {
+            Ok(false)
+        } else {
+            self.write(state, &bytes).await.map(|()| true)
+        }
+    }
+    /// Writes the current in-memory value to the cache file,
+    /// regardless of whether it has been changed
+    pub async fn save(&self) -> Result<()> {
+        let mut state = self.state.lock().await;
+        let bytes = Self::serialize(&*self.inner.read().await).await?;
+        self.write(&mut state, &bytes).await
+    }
+    /// Saves the inner value only if its serialized form differs from
+    /// what was last loaded from or saved to the cache file
+    ///
+    /// Returns whether the file was written.
+    pub async fn save_if_dirty(&self) -> Result<bool> {
+        let mut state = self.state.lock().await;
+        let inner = self.inner.read().await;
+        self.write_if_dirty(&mut state, &inner).await
+    }
+    /// Get a read guard to the inner type
+    pub async fn get(&self) -> Result<RwLockReadGuard<'_, T>> {
+        let _state = self.check_load().await?;
+        Ok(self.inner.read().await)
+    }
+    /// Applies the given closure to a mutable reference to the inner value
+    /// and automatically saves the state to the cache file afterwards,
+    /// unless the closure left the serialized value unchanged
+    ///
+    /// If the save fails after the closure was run, the closure's result is still
+    /// available from the error, see [`MutError::Unsaved`].
+    pub async fn do_mut<R, F: FnOnce(&mut T) -> R>(&self, f: F) -> StdResult<R, MutError<R>> {
+        let mut state = self.check_load().await.map_err(MutError::NotRun)?;
+        let mut inner = self.inner.write().await;
+        let r = f(&mut inner);
+        match self.write_if_dirty(&mut state, &inner).await {
+            Ok(_) => Ok(r),
+            Err(e) => Err(MutError::Unsaved(r, e)),
+        }
+    }
+    /// Consumes the instance, and returns the inner `T`.
+    pub fn into_inner(self) -> T {
+        self.inner.into_inner()
+    }
+}
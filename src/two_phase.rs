@@ -0,0 +1,108 @@
+//! Saving several caches together, see [`commit_all`]
+
+use std::{fmt, io};
+
+use crate::{Cache, CacheError, Cached, Result, persist, storage::Storage};
+
+/// A cache that can be saved in two phases, for committing the saves of several caches together
+/// with [`commit_all`]
+///
+/// This is object-safe, so caches of different types can be committed together.
+pub trait TwoPhase {
+    /// Writes the in-memory value to the storage and makes it durable without replacing the stored data,
+    /// which happens when the returned save is committed
+    ///
+    /// The storage stays locked until the save is committed or dropped,
+    /// and dropping it leaves the stored data as it was.
+    /// Fails with [`Unsupported`](io::ErrorKind::Unsupported) if the storage would change the stored data
+    /// while writing, see [`Storage::writes_in_place`], such as without atomic writes.
+    fn prepare(&self) -> Result<PreparedSave<'_>>;
+}
+
+/// A save written by [`TwoPhase::prepare`] that still has to be committed
+#[must_use = "the save is discarded unless it is committed"]
+pub struct PreparedSave<'a> {
+    commit: Box<dyn FnOnce() -> Result<()> + 'a>,
+}
+
+impl PreparedSave<'_> {
+    #[inline]
+    /// Replaces the stored data with what was written
+    pub fn commit(self) -> Result<()> {
+        (self.commit)()
+    }
+}
+
+impl fmt::Debug for PreparedSave<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        f.write_str("PreparedSave(..)")
+    }
+}
+
+/// Saves all of the caches, only replacing any of their stored data once all of them
+/// have been written and synced to disk
+///
+/// If writing any of them fails, none are replaced. With [`FileStorage`](crate::storage::FileStorage),
+/// this needs atomic writes, failing before anything is written without them,
+/// and replacing is done by renaming the temporary files into place,
+/// which is quick and doesn't need more space, but isn't atomic across files,
+/// so a crash while renaming can still leave only some of the caches saved.
+///
+/// The caches are locked in the given order, so they should always be given in the same order
+/// to avoid deadlocks, and the same cache mustn't be given twice.
+pub fn commit_all(caches: &[&dyn TwoPhase]) -> Result<()> {
+    let prepared = caches.iter().map(|cache| cache.prepare()).collect::<Result<Vec<_>>>()?;
+    prepared.into_iter().try_for_each(PreparedSave::commit)
+}
+
+impl<T: Cache, S: Storage> TwoPhase for Cached<T, S> {
+    fn prepare(&self) -> Result<PreparedSave<'_>> {
+        if !self.is_persistent() {
+            return Ok(PreparedSave { commit: Box::new(|| Ok(())) });
+        }
+        if self.storage.writes_in_place() {
+            return Err(CacheError::Save(io::Error::new(
+                io::ErrorKind::Unsupported,
+                "saves can't be prepared without replacing the stored data, since it's written in place",
+            )));
+        }
+        let lock = self.lock()?;
+        self.trim()?;
+        let inner = self.borrow()?;
+        self.validate(&inner)?;
+        // A full save, so appending can carry on from what is saved
+        let snapshot = self.snapshot.map(|snapshot| snapshot(&inner));
+        let baseline = self.changed.as_ref().map(|predicate| (predicate.clone)(&inner));
+        let prepared = persist::prepare(&self.storage, &*inner, self.codec.as_ref(), self.options.buffer_size);
+        drop(inner);
+        let (writer, hash) = match prepared {
+            Ok(prepared) => prepared,
+            Err(e) => {
+                self.count(|stats| stats.save_errors += 1);
+                return Err(e);
+            }
+        };
+
+        Ok(PreparedSave {
+            commit: Box::new(move || {
+                let committed = writer.commit().map_err(CacheError::Save);
+                self.count(|stats| match committed {
+                    Ok(_) => stats.saves += 1,
+                    Err(_) => stats.save_errors += 1,
+                });
+                let metadata = committed?;
+                if snapshot.is_some() {
+                    *self.last_saved.borrow_mut() = snapshot;
+                }
+                if baseline.is_some() {
+                    *self.baseline.borrow_mut() = baseline;
+                }
+                self.save_deadline.set(None);
+                self.last_written.set(Some(self.now()));
+                self.set_synced(hash, &metadata);
+                drop(lock);
+                Ok(())
+            }),
+        })
+    }
+}
@@ -0,0 +1,182 @@
+use std::{
+    fs::{self, File},
+    io::{self, Read, Write},
+    sync::atomic::{AtomicBool, Ordering},
+    thread,
+};
+
+use cached_struct::{Cache, CacheError, Cached, MutError, commit_all};
+
+mod common;
+use common::temp_path;
+
+/// A run of one repeated letter ending in a `.`, so that partially written data fails to load
+#[derive(Debug, Default)]
+struct Run(String);
+
+impl Cache for Run {
+    fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{}.", self.0)
+    }
+    fn load<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        match s.strip_suffix('.') {
+            Some(run) if run.chars().all(|c| run.starts_with(c)) => Ok(Run(run.to_owned())),
+            _ => Err(io::Error::new(io::ErrorKind::InvalidData, format!("not a whole run: {:?}", s))),
+        }
+    }
+}
+
+#[test]
+fn replaces_file_open_for_reading() {
+    let path = temp_path("replace-open");
+    fs::write(&path, "old").unwrap();
+    let cached = Cached::<String>::new(&path).unwrap();
+
+    let mut reader = File::open(&path).unwrap();
+    cached.set("new".to_owned()).unwrap();
+
+    // The reader still sees the file it opened
+    let mut s = String::new();
+    reader.read_to_string(&mut s).unwrap();
+    assert_eq!(s, "old");
+    drop(reader);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "new");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn concurrent_reads_see_whole_saves() {
+    let path = temp_path("concurrent");
+    let done = AtomicBool::new(false);
+
+    thread::scope(|s| {
+        s.spawn(|| {
+            let writer = Cached::<Run>::new(&path).unwrap();
+            for i in 0..500 {
+                let letter = if i % 2 == 0 { "a" } else { "b" };
+                writer.set(Run(letter.repeat(1 + i % 7 * 1000))).unwrap();
+            }
+            done.store(true, Ordering::Relaxed);
+        });
+        s.spawn(|| {
+            let reader = Cached::<Run>::new(&path).unwrap();
+            while !done.load(Ordering::Relaxed) {
+                // Reloading every time, since the modification time might not change between saves
+                match reader.reload() {
+                    Err(e) if e.kind() == io::ErrorKind::NotFound => (),
+                    r => r.unwrap(),
+                }
+            }
+        });
+    });
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn concurrent_saves_in_one_process_dont_share_temp_files() {
+    let path = temp_path("concurrent-saves");
+
+    thread::scope(|s| {
+        for letter in ["a", "b"] {
+            let path = &path;
+            s.spawn(move || {
+                let writer = Cached::<Run>::lazy(path);
+                for i in 0..200 {
+                    writer.set(Run(letter.repeat(1 + i % 7 * 1000))).unwrap();
+                }
+            });
+        }
+    });
+    Cached::<Run>::new(&path).unwrap();
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn commit_all_replaces_nothing_if_a_save_fails() {
+    let a_path = temp_path("atomic-commit-a");
+    let b_path = temp_path("atomic-commit-b");
+    fs::write(&a_path, "a").unwrap();
+    fs::write(&b_path, "b").unwrap();
+
+    let a = Cached::<String>::new(&a_path).unwrap();
+    let b = Cached::<String>::new(&b_path).unwrap();
+    *a.borrow_mut_raw().unwrap() = "a2".to_owned();
+    *b.borrow_mut_raw().unwrap() = "b2".to_owned();
+    commit_all(&[&a, &b]).unwrap();
+    assert_eq!(fs::read_to_string(&a_path).unwrap(), "a2");
+    assert_eq!(fs::read_to_string(&b_path).unwrap(), "b2");
+    assert!(!a.is_dirty().unwrap());
+
+    // Saving to a missing directory fails before anything is replaced
+    let missing = Cached::<String>::lazy(temp_path("atomic-commit-missing").join("file"));
+    *a.borrow_mut_raw().unwrap() = "a3".to_owned();
+    assert!(commit_all(&[&a, &missing]).is_err());
+    assert_eq!(fs::read_to_string(&a_path).unwrap(), "a2");
+    assert!(a.is_dirty().unwrap());
+
+    // Writing in place would replace the data while preparing, so it fails before anything is written
+    let in_place = Cached::<String>::builder(&b_path).atomic_writes(false).build().unwrap();
+    *in_place.borrow_mut_raw().unwrap() = "b3".to_owned();
+    match commit_all(&[&a, &in_place]) {
+        Err(CacheError::Save(e)) => assert_eq!(e.kind(), io::ErrorKind::Unsupported),
+        r => panic!("expected an unsupported error, got {:?}", r),
+    }
+    assert_eq!(fs::read_to_string(&a_path).unwrap(), "a2");
+    assert_eq!(fs::read_to_string(&b_path).unwrap(), "b2");
+
+    fs::remove_file(&a_path).unwrap();
+    fs::remove_file(&b_path).unwrap();
+}
+
+#[test]
+fn commit_all_with_shared_temp_dir() {
+    let dir = temp_path("atomic-temp-dir");
+    let (a_dir, b_dir, temp_dir) = (dir.join("a"), dir.join("b"), dir.join("temp"));
+    for dir in [&a_dir, &b_dir, &temp_dir] {
+        fs::create_dir_all(dir).unwrap();
+    }
+
+    // Both named the same
+    let a = Cached::<String>::builder(a_dir.join("data")).temp_dir(&temp_dir).build_lazy();
+    let b = Cached::<String>::builder(b_dir.join("data")).temp_dir(&temp_dir).build_lazy();
+    *a.borrow_mut_raw().unwrap() = "a".to_owned();
+    *b.borrow_mut_raw().unwrap() = "b".to_owned();
+    commit_all(&[&a, &b]).unwrap();
+    assert_eq!(fs::read_to_string(a_dir.join("data")).unwrap(), "a");
+    assert_eq!(fs::read_to_string(b_dir.join("data")).unwrap(), "b");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn failed_validation_leaves_file_unchanged() {
+    let path = temp_path("atomic-validate");
+    fs::write(&path, "valid").unwrap();
+
+    let cached = Cached::<String>::builder(&path)
+        .validate(|text| match text.starts_with("valid") {
+            true => Ok(()),
+            false => Err(io::Error::new(io::ErrorKind::InvalidInput, "must start with \"valid\"")),
+        })
+        .build()
+        .unwrap();
+    cached.with_mut(|text| text.push_str(" still")).unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "valid still");
+
+    match cached.with_mut(|text| *text = "broken".to_owned()) {
+        Err(MutError::Unsaved((), CacheError::Invalid(e))) => assert_eq!(e.kind(), io::ErrorKind::InvalidInput),
+        r => panic!("expected a validation error, got {:?}", r),
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "valid still");
+    assert!(matches!(commit_all(&[&cached]), Err(CacheError::Invalid(_))));
+
+    cached.reload().unwrap();
+    assert_eq!(*cached.get().unwrap(), "valid still");
+
+    fs::remove_file(&path).unwrap();
+}
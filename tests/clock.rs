@@ -0,0 +1,92 @@
+use std::time::Duration;
+
+use cached_struct::{Cached, FakeClock, storage::MemoryStorage};
+
+#[test]
+fn debounced_save_waits_for_clock() {
+    let storage = MemoryStorage::new();
+    let clock = FakeClock::new();
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone())
+        .debounce(Duration::from_secs(5))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+
+    cached.with_mut(|text| text.push_str("hello")).unwrap();
+    clock.advance(Duration::from_secs(4));
+    cached.get().unwrap();
+    assert_eq!(storage.data(), None);
+
+    clock.advance(Duration::from_secs(1));
+    cached.get().unwrap();
+    assert_eq!(storage.data().as_deref(), Some(&b"hello"[..]));
+}
+
+#[test]
+fn ttl_expires_with_clock() {
+    let storage = MemoryStorage::with_data("a");
+    let clock = FakeClock::new();
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone())
+        .ttl(Duration::from_secs(10))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    assert_eq!(cached.reload_count(), 1);
+
+    clock.advance(Duration::from_secs(9));
+    cached.get().unwrap();
+    assert_eq!(cached.reload_count(), 1);
+
+    clock.advance(Duration::from_secs(2));
+    cached.get().unwrap();
+    assert_eq!(cached.reload_count(), 2);
+}
+
+#[test]
+fn check_interval_waits_for_clock() {
+    let storage = MemoryStorage::with_data("a");
+    let clock = FakeClock::new();
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone())
+        .check_interval(Duration::from_secs(10))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+    assert_eq!(*cached.get().unwrap(), "a");
+
+    storage.set_data("b");
+    clock.advance(Duration::from_secs(9));
+    assert_eq!(*cached.get().unwrap(), "a");
+
+    clock.advance(Duration::from_secs(1));
+    assert_eq!(*cached.get().unwrap(), "b");
+}
+
+#[test]
+fn throttled_saves_skip_intermediate_values() {
+    let storage = MemoryStorage::new();
+    let clock = FakeClock::new();
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone())
+        .max_save_rate(Duration::from_secs(5))
+        .clock(clock.clone())
+        .build()
+        .unwrap();
+
+    cached.with_mut(|text| text.push('a')).unwrap();
+    assert_eq!(storage.data().as_deref(), Some(&b"a"[..]));
+    assert!(!cached.pending_save());
+
+    cached.with_mut(|text| text.push('b')).unwrap();
+    clock.advance(Duration::from_secs(3));
+    cached.with_mut(|text| text.push('c')).unwrap();
+    assert_eq!(storage.data().as_deref(), Some(&b"a"[..]));
+    assert!(cached.pending_save());
+
+    clock.advance(Duration::from_secs(2));
+    cached.get().unwrap();
+    assert_eq!(storage.data().as_deref(), Some(&b"abc"[..]));
+    assert!(!cached.pending_save());
+
+    cached.with_mut(|text| text.push('d')).unwrap();
+    drop(cached);
+    assert_eq!(storage.data().as_deref(), Some(&b"abcd"[..]));
+}
@@ -0,0 +1,22 @@
+#![allow(dead_code)]
+
+use std::{io, path::PathBuf};
+
+use cached_struct::Cache;
+
+/// A path in the temporary directory that is unique to the test and the process
+pub fn temp_path(name: &str) -> PathBuf {
+    std::env::temp_dir().join(format!("cached-struct-{}-{}", name, std::process::id()))
+}
+
+/// The bytes saved by the value
+pub fn saved<C: Cache>(value: &C) -> Vec<u8> {
+    let mut bytes = Vec::new();
+    value.save(&mut bytes).unwrap();
+    bytes
+}
+
+/// The error from loading the bytes, which must fail
+pub fn load_error<C: Cache>(bytes: &[u8]) -> io::Error {
+    C::load(bytes).err().expect("loading should fail")
+}
@@ -0,0 +1,94 @@
+#![cfg(feature = "sha2")]
+
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use cached_struct::{Cached, storage::ContentAddressed};
+use filetime::FileTime;
+
+mod common;
+use common::temp_path;
+
+/// Saves the value and backdates its blob by the given number of minutes, so that the snapshots are ordered
+fn save_at(cached: &Cached<String, ContentAddressed>, storage: &ContentAddressed, value: &str, minutes_ago: u64) {
+    cached.set(value.to_owned()).unwrap();
+    let blob = storage.blob_path(&storage.current().unwrap().unwrap());
+    let modified = SystemTime::now() - Duration::from_secs(60 * minutes_ago);
+    filetime::set_file_mtime(blob, FileTime::from_system_time(modified)).unwrap();
+}
+
+#[test]
+fn same_data_is_stored_once() {
+    let dir = temp_path("content-dedup");
+    let storage = ContentAddressed::new(&dir);
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone()).build().unwrap();
+
+    cached.set("a".to_owned()).unwrap();
+    let a = storage.current().unwrap().unwrap();
+    cached.set("b".to_owned()).unwrap();
+    cached.set("a".to_owned()).unwrap();
+    assert_eq!(storage.current().unwrap(), Some(a.clone()));
+    assert_eq!(storage.snapshots().unwrap().len(), 2);
+    assert_eq!(fs::read_to_string(storage.blob_path(&a)).unwrap(), "a");
+
+    // Only the blobs and the pointer are left, not any temporary files
+    let mut names: Vec<_> = fs::read_dir(&dir).unwrap().map(|e| e.unwrap().file_name().into_string().unwrap()).collect();
+    names.retain(|name| name != ".lock");
+    assert_eq!(names.len(), 3);
+
+    let reopened = Cached::<String, _>::builder_with_storage(storage).build().unwrap();
+    assert_eq!(*reopened.get().unwrap(), "a");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn snapshots_are_newest_first() {
+    let dir = temp_path("content-snapshots");
+    let storage = ContentAddressed::new(&dir);
+    assert!(storage.snapshots().unwrap().is_empty());
+    assert_eq!(storage.current().unwrap(), None);
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone()).build().unwrap();
+
+    save_at(&cached, &storage, "old", 2);
+    save_at(&cached, &storage, "older", 3);
+    save_at(&cached, &storage, "new", 1);
+    let contents: Vec<_> = storage
+        .snapshots()
+        .unwrap()
+        .into_iter()
+        .map(|(_, digest)| fs::read_to_string(storage.blob_path(&digest)).unwrap())
+        .collect();
+    assert_eq!(contents, ["new", "old", "older"]);
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn garbage_collection_keeps_current_and_newest() {
+    let dir = temp_path("content-gc");
+    let storage = ContentAddressed::new(&dir);
+    assert_eq!(storage.collect_garbage(0).unwrap(), 0);
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone()).build().unwrap();
+
+    save_at(&cached, &storage, "a", 3);
+    save_at(&cached, &storage, "b", 2);
+    save_at(&cached, &storage, "c", 1);
+    // The current blob is the oldest one, and doesn't count towards those kept
+    save_at(&cached, &storage, "a", 4);
+    let current = storage.current().unwrap().unwrap();
+
+    assert_eq!(storage.collect_garbage(1).unwrap(), 1);
+    let left: Vec<_> = storage.snapshots().unwrap().into_iter().map(|(_, digest)| digest).collect();
+    assert_eq!(left.len(), 2);
+    assert!(left.contains(&current));
+    assert_eq!(fs::read_to_string(storage.blob_path(&left[0])).unwrap(), "c");
+
+    assert_eq!(storage.collect_garbage(0).unwrap(), 1);
+    assert_eq!(storage.snapshots().unwrap().len(), 1);
+    assert_eq!(*cached.get().unwrap(), "a");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
@@ -0,0 +1,44 @@
+use std::panic::{self, AssertUnwindSafe};
+
+use cached_struct::{CacheError, Cached, storage::MemoryStorage};
+
+mod common;
+use common::temp_path;
+
+#[test]
+fn dropping_the_guard_saves() {
+    let storage = MemoryStorage::new();
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone()).build().unwrap();
+
+    let mut guard = cached.edit().unwrap();
+    guard.push_str("hello");
+    guard.push_str(" world");
+    assert_eq!(storage.data(), None);
+    drop(guard);
+    assert_eq!(storage.data().as_deref(), Some(&b"hello world"[..]));
+    assert_eq!(cached.stats().saves, 1);
+}
+
+#[test]
+fn commit_returns_save_error() {
+    let cached = Cached::<String>::lazy(temp_path("edit-missing").join("file"));
+
+    let mut guard = cached.edit().unwrap();
+    guard.push_str("unsaved");
+    assert!(matches!(guard.commit(), Err(CacheError::Save(_))));
+    assert!(cached.is_dirty().unwrap());
+}
+
+#[test]
+fn panicking_edit_isnt_saved() {
+    let storage = MemoryStorage::with_data("before");
+    let cached = Cached::<String, _>::builder_with_storage(storage.clone()).build().unwrap();
+
+    let edited = panic::catch_unwind(AssertUnwindSafe(|| {
+        let mut guard = cached.edit().unwrap();
+        guard.push_str(" half");
+        panic!("edit failed");
+    }));
+    assert!(edited.is_err());
+    assert_eq!(storage.data().as_deref(), Some(&b"before"[..]));
+}
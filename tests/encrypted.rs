@@ -0,0 +1,58 @@
+#![cfg(feature = "encryption")]
+
+use std::io::ErrorKind;
+
+use cached_struct::{Cache, formats::{Encrypted, EncryptionKey}};
+
+mod common;
+use common::{load_error, saved};
+
+struct Key;
+
+impl EncryptionKey for Key {
+    fn key() -> [u8; 32] {
+        [7; 32]
+    }
+}
+
+struct OtherKey;
+
+impl EncryptionKey for OtherKey {
+    fn key() -> [u8; 32] {
+        [8; 32]
+    }
+}
+
+#[test]
+fn encrypted_round_trips() {
+    let bytes = saved(&Encrypted::<String, Key>::new("hello".to_owned()));
+    assert!(!bytes.windows(5).any(|w| w == b"hello"));
+    assert_eq!(Encrypted::<String, Key>::load(&bytes[..]).unwrap().into_inner(), "hello");
+}
+
+#[test]
+fn encrypted_uses_a_new_nonce_for_each_save() {
+    let value = Encrypted::<String, Key>::new("hello".to_owned());
+    assert_ne!(saved(&value), saved(&value));
+}
+
+#[test]
+fn encrypted_rejects_wrong_key() {
+    let bytes = saved(&Encrypted::<String, Key>::new("hello".to_owned()));
+    let e = load_error::<Encrypted<String, OtherKey>>(&bytes);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn encrypted_rejects_tampered_data() {
+    let mut bytes = saved(&Encrypted::<String, Key>::new("hello".to_owned()));
+    *bytes.last_mut().unwrap() ^= 1;
+    let e = load_error::<Encrypted<String, Key>>(&bytes);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn encrypted_rejects_truncated_nonce() {
+    let e = load_error::<Encrypted<String, Key>>(&[0; 11]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
@@ -0,0 +1,192 @@
+use std::{
+    fs,
+    io::{self, ErrorKind, Read, Write},
+};
+
+use cached_struct::{
+    Cache, Cached,
+    formats::{
+        CHECKSUM_HEADER_LEN, Checksummed, Converted, Migrate, Tagged, TaggedCache,
+        VERSION_HEADER_LEN, VERSION_MAGIC, Versioned,
+    },
+};
+
+mod common;
+use common::{load_error, saved, temp_path};
+
+/// Saved in upper case since version 2
+#[derive(Debug, Default)]
+struct Upper(String);
+
+impl Cache for Upper {
+    fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(self.0.as_bytes())
+    }
+    fn load<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        Ok(Upper(s))
+    }
+}
+
+impl Migrate for Upper {
+    const VERSION: u32 = 2;
+    fn migrate<R: Read>(_version: u32, reader: R) -> io::Result<Self> {
+        Upper::load(reader).map(|old| Upper(old.0.to_uppercase()))
+    }
+}
+
+#[derive(Debug, Default)]
+struct Name(String);
+
+impl From<Versioned<Upper>> for Name {
+    fn from(stored: Versioned<Upper>) -> Self {
+        Name(stored.into_inner().0)
+    }
+}
+
+impl From<&Name> for Versioned<Upper> {
+    fn from(name: &Name) -> Self {
+        Versioned::new(Upper(name.0.clone()))
+    }
+}
+
+fn versioned(version: u32, data: &str) -> Vec<u8> {
+    let mut bytes = VERSION_MAGIC.to_vec();
+    bytes.extend_from_slice(&version.to_le_bytes());
+    bytes.extend_from_slice(data.as_bytes());
+    bytes
+}
+
+#[test]
+fn converted_rewrites_migrated_data() {
+    let path = temp_path("formats-converted-migrate");
+    fs::write(&path, versioned(1, "bob")).unwrap();
+
+    let cached = Cached::<Converted<Versioned<Upper>, Name>>::new(&path).unwrap();
+    assert_eq!(cached.get().unwrap().0, "BOB");
+    assert_eq!(fs::read(&path).unwrap(), versioned(2, "BOB"));
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn checksummed_round_trips() {
+    let bytes = saved(&Checksummed("hello".to_owned()));
+    assert_eq!(bytes.len(), CHECKSUM_HEADER_LEN + 5);
+    assert_eq!(Checksummed::<String>::load(&bytes[..]).unwrap().0, "hello");
+}
+
+#[test]
+fn checksummed_rejects_truncated_header() {
+    let bytes = saved(&Checksummed("hello".to_owned()));
+    let e = load_error::<Checksummed<String>>(&bytes[..CHECKSUM_HEADER_LEN - 1]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn checksummed_rejects_truncated_data() {
+    let bytes = saved(&Checksummed("hello".to_owned()));
+    let e = load_error::<Checksummed<String>>(&bytes[..bytes.len() - 1]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+}
+
+#[test]
+fn checksummed_rejects_crc_mismatch() {
+    let mut bytes = saved(&Checksummed("hello".to_owned()));
+    *bytes.last_mut().unwrap() ^= 1;
+    let e = load_error::<Checksummed<String>>(&bytes);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "checksum mismatch");
+}
+
+#[test]
+fn versioned_round_trips() {
+    let bytes = saved(&Versioned::new(Upper("BOB".to_owned())));
+    assert_eq!(bytes, versioned(2, "BOB"));
+    let loaded = Versioned::<Upper>::load(&bytes[..]).unwrap();
+    assert_eq!(loaded.migrated_from(), None);
+    assert!(!loaded.needs_rewrite());
+    assert_eq!(loaded.into_inner().0, "BOB");
+}
+
+#[test]
+fn versioned_migrates_older_versions() {
+    let loaded = Versioned::<Upper>::load(&versioned(1, "bob")[..]).unwrap();
+    assert_eq!(loaded.migrated_from(), Some(1));
+    assert!(loaded.needs_rewrite());
+    assert_eq!(loaded.into_inner().0, "BOB");
+}
+
+#[test]
+fn versioned_rejects_newer_versions() {
+    let e = load_error::<Versioned<Upper>>(&versioned(3, "bob"));
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "version 3 is newer than the supported version 2");
+}
+
+#[test]
+fn versioned_rejects_truncated_header() {
+    let e = load_error::<Versioned<Upper>>(&versioned(2, "")[..VERSION_HEADER_LEN - 1]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "version header is truncated");
+}
+
+#[test]
+fn versioned_rejects_missing_header() {
+    let e = load_error::<Versioned<Upper>>(b"unversioned data");
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "version header is missing");
+}
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum Shape {
+    Circle(u8),
+    Square(u8, u8),
+}
+
+impl TaggedCache for Shape {
+    fn tag(&self) -> u8 {
+        match self {
+            Shape::Circle(_) => 1,
+            Shape::Square(..) => 2,
+        }
+    }
+    fn save_variant<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        match *self {
+            Shape::Circle(r) => writer.write_all(&[r]),
+            Shape::Square(w, h) => writer.write_all(&[w, h]),
+        }
+    }
+    fn load_variant<R: Read>(tag: u8, mut reader: R) -> io::Result<Option<Self>> {
+        let mut data = [0; 2];
+        match tag {
+            1 => reader.read_exact(&mut data[..1]).map(|()| Some(Shape::Circle(data[0]))),
+            2 => reader.read_exact(&mut data).map(|()| Some(Shape::Square(data[0], data[1]))),
+            _ => Ok(None),
+        }
+    }
+}
+
+#[test]
+fn tagged_round_trips() {
+    for shape in [Shape::Circle(4), Shape::Square(2, 3)] {
+        let bytes = saved(&Tagged(shape));
+        assert_eq!(Tagged::<Shape>::load(&bytes[..]).unwrap(), Tagged(shape));
+    }
+    assert_eq!(saved(&Tagged(Shape::Square(2, 3))), [2, 2, 3]);
+}
+
+#[test]
+fn tagged_rejects_missing_tag() {
+    let e = load_error::<Tagged<Shape>>(&[]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "variant tag is missing");
+}
+
+#[test]
+fn tagged_rejects_unknown_tag() {
+    let e = load_error::<Tagged<Shape>>(&[9, 1]);
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert_eq!(e.to_string(), "unknown variant tag 0x09");
+}
@@ -0,0 +1,50 @@
+#![cfg(feature = "gzip")]
+
+use std::io::ErrorKind;
+
+use cached_struct::{Cache, formats::{AutoDecompress, Gzip}};
+
+mod common;
+use common::{load_error, saved};
+
+#[test]
+fn gzip_round_trips() {
+    let bytes = saved(&Gzip::<String>("hello".repeat(100)));
+    assert!(bytes.len() < 500);
+    assert_eq!(Gzip::<String>::load(&bytes[..]).unwrap().0, "hello".repeat(100));
+}
+
+#[test]
+fn gzip_round_trips_uncompressed_level() {
+    let bytes = saved(&Gzip::<String, 0>("hello".to_owned()));
+    assert_eq!(Gzip::<String, 0>::load(&bytes[..]).unwrap().0, "hello");
+}
+
+#[test]
+fn gzip_rejects_data_that_isnt_compressed() {
+    load_error::<Gzip<String>>(b"hello");
+}
+
+#[test]
+fn gzip_rejects_truncated_data() {
+    let bytes = saved(&Gzip::<String>("hello".to_owned()));
+    let e = load_error::<Gzip<String>>(&bytes[..bytes.len() - 4]);
+    assert_eq!(e.kind(), ErrorKind::UnexpectedEof);
+}
+
+#[test]
+fn gzip_rejects_corrupt_data() {
+    let mut bytes = saved(&Gzip::<String>("hello".to_owned()));
+    let crc = bytes.len() - 8;
+    bytes[crc] ^= 1;
+    load_error::<Gzip<String>>(&bytes);
+}
+
+#[test]
+fn auto_decompress_loads_compressed_and_plain_data() {
+    let bytes = saved(&AutoDecompress::<String>("hello".to_owned()));
+    assert_eq!(bytes, saved(&Gzip::<String>("hello".to_owned())));
+    assert_eq!(AutoDecompress::<String>::load(&bytes[..]).unwrap().0, "hello");
+    assert_eq!(AutoDecompress::<String>::load(&b"hello"[..]).unwrap().0, "hello");
+    assert_eq!(AutoDecompress::<String>::load(&b"h"[..]).unwrap().0, "h");
+}
@@ -0,0 +1,41 @@
+#![cfg(feature = "serde")]
+
+use std::io::ErrorKind;
+
+use cached_struct::{Cache, StreamingCache, formats::Jsonl};
+
+mod common;
+use common::{load_error, saved};
+
+type Entries = Jsonl<Vec<(String, u32)>>;
+
+fn entries() -> Entries {
+    Jsonl(vec![("alice".to_owned(), 3), ("bob".to_owned(), 5)])
+}
+
+#[test]
+fn jsonl_round_trips() {
+    let bytes = saved(&entries());
+    assert_eq!(bytes, b"[\"alice\",3]\n[\"bob\",5]\n");
+    assert_eq!(Entries::load(&bytes[..]).unwrap(), entries());
+}
+
+#[test]
+fn jsonl_skips_blank_lines() {
+    let loaded = Entries::load(&b"\n[\"alice\",3]\n  \n[\"bob\",5]\n\n"[..]).unwrap();
+    assert_eq!(loaded, entries());
+}
+
+#[test]
+fn jsonl_streams_records() {
+    let mut names = Vec::new();
+    Entries::load_streaming(&b"[\"alice\",3]\n[\"bob\",5]\n"[..], |(name, _)| names.push(name)).unwrap();
+    assert_eq!(names, ["alice", "bob"]);
+}
+
+#[test]
+fn jsonl_reports_the_number_of_an_invalid_line() {
+    let e = load_error::<Entries>(b"[\"alice\",3]\n\n[\"bob\",\"five\"]\n");
+    assert_eq!(e.kind(), ErrorKind::InvalidData);
+    assert!(e.to_string().starts_with("line 3: "), "{}", e);
+}
@@ -0,0 +1,88 @@
+use std::{
+    env,
+    fs::{self, File},
+    io::{self, Read, Write},
+    path::PathBuf,
+    process::{Child, Command, Stdio},
+    time::Duration,
+};
+
+use cached_struct::{Cache, Cached};
+
+mod common;
+use common::temp_path;
+
+/// Set in the child processes to what they should do with the cache file
+const CHILD_VAR: &str = "CACHED_STRUCT_LOCK_CHILD";
+const PATH_VAR: &str = "CACHED_STRUCT_LOCK_PATH";
+
+#[derive(Debug, Default)]
+struct Counter(u32);
+
+impl Cache for Counter {
+    fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{}", self.0)
+    }
+    fn load<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        s.parse().map(Counter).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+/// Runs `child_process` in this test binary as another process
+fn spawn(mode: &str, path: &PathBuf) -> Child {
+    Command::new(env::current_exe().unwrap())
+        .args(["--exact", "child_process", "--test-threads=1"])
+        .env(CHILD_VAR, mode)
+        .env(PATH_VAR, path)
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap()
+}
+
+/// What the child processes run, which does nothing when run as a test itself
+#[test]
+fn child_process() {
+    let mode = match env::var(CHILD_VAR) {
+        Ok(mode) => mode,
+        Err(_) => return,
+    };
+    let cached = Cached::<Counter>::builder(env::var(PATH_VAR).unwrap())
+        .locking(true)
+        .lock_timeout(Duration::from_millis(200))
+        .build_lazy();
+    match &*mode {
+        "read" => assert_eq!(cached.get().unwrap().0, 1),
+        "blocked-write" => {
+            let e = cached.with_mut(|counter| counter.0 += 1).unwrap_err();
+            assert_eq!(e.error().kind(), io::ErrorKind::TimedOut);
+        }
+        "write" => cached.with_mut(|counter| counter.0 += 1).unwrap(),
+        mode => panic!("unknown mode {}", mode),
+    }
+}
+
+#[test]
+fn readers_share_the_lock_but_writers_are_exclusive() {
+    let path = temp_path("lock-shared");
+    let lock_path = PathBuf::from(format!("{}.lock", path.display()));
+    fs::write(&path, "1").unwrap();
+
+    // Held like a reader in another process would
+    let reader = File::create(&lock_path).unwrap();
+    reader.lock_shared().unwrap();
+    let readers: Vec<_> = (0..3).map(|_| spawn("read", &path)).collect();
+    let writer = spawn("blocked-write", &path);
+    for mut child in readers.into_iter().chain([writer]) {
+        assert!(child.wait().unwrap().success());
+    }
+    assert_eq!(fs::read_to_string(&path).unwrap(), "1");
+
+    reader.unlock().unwrap();
+    assert!(spawn("write", &path).wait().unwrap().success());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "2");
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&lock_path).unwrap();
+}
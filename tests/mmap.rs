@@ -0,0 +1,56 @@
+#![cfg(feature = "mmap")]
+
+use std::{
+    fs,
+    io::{self, ErrorKind},
+};
+
+use cached_struct::{Cache, CacheError, Cached, formats::{Mapped, MmapCache}};
+
+mod common;
+use common::temp_path;
+
+#[derive(Debug, Default, Clone)]
+struct Bytes(Vec<u8>);
+
+impl Cache for Bytes {
+    fn save<W: io::Write>(&self, mut writer: W) -> io::Result<()> {
+        writer.write_all(&self.0)
+    }
+    fn load<R: io::Read>(mut reader: R) -> io::Result<Self> {
+        let mut data = Vec::new();
+        reader.read_to_end(&mut data)?;
+        Ok(Bytes(data))
+    }
+}
+
+impl MmapCache for Bytes {
+    fn from_mmap(map: &[u8]) -> io::Result<Self> {
+        Ok(Bytes(map.to_owned()))
+    }
+}
+
+#[test]
+fn loads_mapped_file() {
+    let path = temp_path("mmap-load");
+    fs::write(&path, "mapped").unwrap();
+
+    let cached = Cached::<Mapped<Bytes>>::new(&path).unwrap();
+    assert_eq!(cached.get().unwrap().0 .0, b"mapped");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn refuses_saves_in_place() {
+    let path = temp_path("mmap-in-place");
+    fs::write(&path, "mapped").unwrap();
+
+    let unsupported = |e: CacheError| assert_eq!(e.kind(), ErrorKind::Unsupported);
+    unsupported(Cached::<Mapped<Bytes>>::builder(&path).atomic_writes(false).build().unwrap_err());
+    unsupported(Cached::<Mapped<Bytes>>::builder(&path).append(true).build().unwrap_err());
+    let lazy = Cached::<Mapped<Bytes>>::builder(&path).atomic_writes(false).build_lazy();
+    unsupported(lazy.get().map(drop).unwrap_err());
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,193 @@
+use std::{
+    fs,
+    time::{Duration, SystemTime},
+};
+
+use cached_struct::Cached;
+use filetime::FileTime;
+
+mod common;
+use common::temp_path;
+
+#[test]
+fn reloads_when_mtime_goes_backwards() {
+    let path = temp_path("mtime-backwards");
+    fs::write(&path, "new").unwrap();
+    let now = SystemTime::now();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(now)).unwrap();
+
+    let cached = Cached::<String>::new(&path).unwrap();
+    assert_eq!(*cached.get().unwrap(), "new");
+
+    // Same length, so only the older modification time tells that it changed
+    fs::write(&path, "old").unwrap();
+    let older = now - Duration::from_secs(3600);
+    filetime::set_file_mtime(&path, FileTime::from_system_time(older)).unwrap();
+
+    assert_eq!(*cached.get().unwrap(), "old");
+    assert_eq!(cached.last_modified(), older);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[cfg(unix)]
+#[test]
+fn reloads_when_file_is_replaced_with_same_mtime() {
+    let path = temp_path("mtime-replaced");
+    let replacement = temp_path("mtime-replacement");
+    let mtime = FileTime::from_system_time(SystemTime::now() - Duration::from_secs(3600));
+    fs::write(&path, "old").unwrap();
+    filetime::set_file_mtime(&path, mtime).unwrap();
+
+    let cached = Cached::<String>::new(&path).unwrap();
+    assert_eq!(*cached.get().unwrap(), "old");
+
+    // Same length and modification time, like an editor writing a new file and renaming it over
+    fs::write(&replacement, "new").unwrap();
+    filetime::set_file_mtime(&replacement, mtime).unwrap();
+    fs::rename(&replacement, &path).unwrap();
+
+    assert_eq!(*cached.get().unwrap(), "new");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn touch_updates_mtime_without_reloading() {
+    let path = temp_path("mtime-touch");
+    let _ = fs::remove_file(&path);
+
+    let cached = Cached::<String>::new_with(|| "value".to_owned(), &path).unwrap();
+    cached.touch().unwrap();
+    assert_eq!(fs::read_to_string(&path).unwrap(), "value");
+
+    let old = SystemTime::now() - Duration::from_secs(60);
+    filetime::set_file_mtime(&path, FileTime::from_system_time(old)).unwrap();
+    assert_eq!(*cached.get().unwrap(), "value");
+    cached.touch().unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+    assert!(modified > old + Duration::from_secs(30));
+    assert_eq!(cached.last_modified(), modified);
+    assert_eq!(cached.reload_count(), 1);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn hash_based_reload_ignores_metadata() {
+    let path = temp_path("mtime-hash-based");
+    fs::write(&path, "old").unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+    let cached = Cached::<String>::builder(&path).hash_based_reload(true).build().unwrap();
+    assert_eq!(*cached.get().unwrap(), "old");
+
+    // Same length and modification time, so only the contents tell that it changed
+    fs::write(&path, "new").unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(modified)).unwrap();
+    assert_eq!(*cached.get().unwrap(), "new");
+
+    // Touching the file doesn't change the contents, so it isn't parsed again
+    filetime::set_file_mtime(&path, FileTime::from_system_time(modified + Duration::from_secs(60))).unwrap();
+    assert_eq!(*cached.get().unwrap(), "new");
+    assert_eq!(cached.reload_count(), 2);
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn try_reload_keeps_last_good_value() {
+    let path = temp_path("mtime-try-reload");
+    fs::write(&path, "good").unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+    let cached = Cached::<String>::new(&path).unwrap();
+    assert_eq!(*cached.get().unwrap(), "good");
+    assert!(!cached.try_reload().unwrap());
+
+    // Not valid UTF-8
+    fs::write(&path, b"\xff\xfe").unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(modified + Duration::from_secs(60))).unwrap();
+    assert!(cached.try_reload().is_err());
+    assert_eq!(*cached.peek(), "good");
+    assert_eq!(cached.last_modified(), modified);
+
+    fs::write(&path, "fixed").unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(modified + Duration::from_secs(120))).unwrap();
+    assert!(cached.try_reload().unwrap());
+    assert_eq!(*cached.get().unwrap(), "fixed");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn reopen_loads_replaced_directory() {
+    let dir = temp_path("mtime-reopen");
+    let path = dir.join("data");
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&path, "old").unwrap();
+    let modified = fs::metadata(&path).unwrap().modified().unwrap();
+
+    let cached = Cached::<String>::builder(&path).check_interval(Duration::from_secs(3600)).build().unwrap();
+    assert_eq!(*cached.get().unwrap(), "old");
+
+    // A new directory with a file that was written at the same time
+    fs::remove_dir_all(&dir).unwrap();
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(&path, "new").unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(modified)).unwrap();
+    assert_eq!(*cached.get().unwrap(), "old");
+
+    cached.reopen().unwrap();
+    assert_eq!(*cached.get().unwrap(), "new");
+
+    fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn settle_delay_waits_for_edits_to_finish() {
+    let path = temp_path("mtime-settle");
+    fs::write(&path, "old").unwrap();
+
+    let cached = Cached::<String>::builder(&path).settle_delay(Duration::from_millis(100)).build().unwrap();
+    assert_eq!(*cached.get().unwrap(), "old");
+
+    // Written in two steps, like a text editor might
+    fs::write(&path, "ne").unwrap();
+    let editor = {
+        let path = path.clone();
+        std::thread::spawn(move || {
+            std::thread::sleep(Duration::from_millis(50));
+            fs::write(&path, "new").unwrap();
+        })
+    };
+    assert_eq!(*cached.get().unwrap(), "new");
+    editor.join().unwrap();
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn compare_and_save_fails_after_someone_else_saved() {
+    let path = temp_path("mtime-compare-and-save");
+    fs::write(&path, "ours").unwrap();
+
+    let cached = Cached::<String>::new(&path).unwrap();
+    assert_eq!(*cached.get().unwrap(), "ours");
+    let expected = cached.last_modified();
+
+    fs::write(&path, "theirs").unwrap();
+    filetime::set_file_mtime(&path, FileTime::from_system_time(expected + Duration::from_secs(60))).unwrap();
+    assert!(!cached.compare_and_save(expected).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "theirs");
+
+    // A change that kept the modification time is told by the length
+    filetime::set_file_mtime(&path, FileTime::from_system_time(expected)).unwrap();
+    assert!(!cached.compare_and_save(expected).unwrap());
+
+    assert_eq!(*cached.get().unwrap(), "theirs");
+    assert!(cached.compare_and_save(cached.last_modified()).unwrap());
+    assert_eq!(fs::read_to_string(&path).unwrap(), "theirs");
+
+    fs::remove_file(&path).unwrap();
+}
@@ -0,0 +1,83 @@
+use std::{
+    fs,
+    io::{self, Read, Write},
+    path::PathBuf,
+    sync::{Arc, Mutex},
+    thread,
+    time::Duration,
+};
+
+use cached_struct::{Cache, Cached, sync::SyncCached};
+
+mod common;
+use common::temp_path;
+
+#[derive(Debug, Default)]
+struct Counter(u32);
+
+impl Cache for Counter {
+    fn save<W: Write>(&self, mut writer: W) -> io::Result<()> {
+        write!(writer, "{}", self.0)
+    }
+    fn load<R: Read>(mut reader: R) -> io::Result<Self> {
+        let mut s = String::new();
+        reader.read_to_string(&mut s)?;
+        s.parse().map(Counter).map_err(|e| io::Error::new(io::ErrorKind::InvalidData, e))
+    }
+}
+
+fn assert_send<T: Send>() {}
+fn assert_send_sync<T: Send + Sync>() {}
+
+#[test]
+fn thread_safety() {
+    assert_send::<Cached<Counter>>();
+    assert_send_sync::<SyncCached<Counter>>();
+    assert_send_sync::<Arc<Mutex<Cached<Counter>>>>();
+}
+
+#[test]
+fn shared_between_threads() {
+    let path = temp_path("shared");
+    let cached = Cached::<Counter>::shared(&path).unwrap();
+
+    let threads: Vec<_> = (0..4)
+        .map(|_| {
+            let cached = cached.clone();
+            thread::spawn(move || {
+                for _ in 0..10 {
+                    cached.lock().unwrap().with_mut(|counter| counter.0 += 1).unwrap();
+                }
+            })
+        })
+        .collect();
+    for thread in threads {
+        thread.join().unwrap();
+    }
+
+    assert_eq!(cached.lock().unwrap().get().unwrap().0, 40);
+    assert_eq!(fs::read_to_string(&path).unwrap(), "40");
+
+    fs::remove_file(&path).unwrap();
+}
+
+#[test]
+fn lock_timeout_gives_up_on_held_lock() {
+    let path = temp_path("lock-timeout");
+    fs::write(&path, "1").unwrap();
+    let lock_path = PathBuf::from(format!("{}.lock", path.display()));
+    let held = fs::File::create(&lock_path).unwrap();
+    held.lock().unwrap();
+
+    let cached = Cached::<Counter>::builder(&path)
+        .locking(true)
+        .lock_timeout(Duration::from_millis(20))
+        .build_lazy();
+    assert_eq!(cached.get().unwrap_err().kind(), io::ErrorKind::TimedOut);
+
+    held.unlock().unwrap();
+    assert_eq!(cached.get().unwrap().0, 1);
+
+    fs::remove_file(&path).unwrap();
+    fs::remove_file(&lock_path).unwrap();
+}